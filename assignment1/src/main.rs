@@ -1,36 +1,183 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, sprite::MaterialMesh2dBundle, utils::HashMap};
+use mathcore::debug_draw::{DebugDraw, DebugDrawPlugin};
+use mathcore::font::{EmbeddedFont, EmbeddedFontPlugin};
+use mathcore::hud::{Hud, HudPlugin};
+use mathcore::mesh::{convex_polygon_mesh, polygon_outline_mesh};
+use mathcore::player::{Player, PlayerControllerPlugin};
+use mathcore::polygon::FillRule;
+use mathcore::sdf::sdf_box;
+use mathcore::trigger::{TriggerEntered, TriggerExited, TriggerPlugin, TriggerShape};
+use std::f32::consts::TAU;
 
 fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
+        .add_plugin(EmbeddedFontPlugin)
+        .add_plugin(DebugDrawPlugin)
+        .add_plugin(HudPlugin::default())
         .add_startup_system(add_rectangle)
+        .add_startup_system(add_other_regions)
         .add_startup_system(add_moving_rectangle)
-        .add_system(move_player)
-        .add_system(check_if_player_in_square)
+        .add_plugin(PlayerControllerPlugin::default())
+        .add_plugin(TriggerPlugin)
+        .init_resource::<BoxMode>()
+        .add_system(switch_box_mode)
+        .add_system(rotate_rectangle)
+        .add_system(update_rect_trigger_shapes)
+        .add_system(color_trigger_regions)
+        .add_system(show_distance_to_rects)
         .run();
 }
 
 #[derive(Component)]
 struct Rect;
 
-fn add_rectangle(mut commands: Commands) {
+// Text showing the signed distance from the closest player to `rect`
+#[derive(Component)]
+struct DistanceLabel {
+    rect: Entity,
+}
+
+fn add_rectangle(mut commands: Commands, font: Res<EmbeddedFont>) {
     commands.spawn_bundle(Camera2dBundle::default());
 
-    commands
+    let rect = commands
         .spawn_bundle(SpriteBundle {
             sprite: Sprite {
                 custom_size: Some(Vec2::new(100.0, 100.0)),
+                color: Color::CRIMSON,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(Rect)
+        .insert(TriggerShape::Aabb {
+            half_extents: Vec2::new(50.0, 50.0),
+        })
+        .id();
+    add_distance_label(&mut commands, &font, rect);
+
+    // A second, thinner rect that starts rotated
+    let rect = commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(Vec2::new(80.0, 160.0)),
+                color: Color::CRIMSON,
                 ..Default::default()
             },
+            transform: Transform::from_xyz(-250.0, 150.0, 0.0)
+                .with_rotation(Quat::from_rotation_z(f32::to_radians(20.0))),
             ..Default::default()
         })
-        .insert(Rect);
+        .insert(Rect)
+        .insert(TriggerShape::Aabb {
+            half_extents: Vec2::new(40.0, 80.0),
+        })
+        .id();
+    add_distance_label(&mut commands, &font, rect);
 }
 
-// The float value is the player movement speed in 'pixels/second'.
-#[derive(Component)]
-struct Player {
-    pub speed: f32,
+fn add_distance_label(commands: &mut Commands, font: &EmbeddedFont, rect: Entity) {
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: font.0.clone(),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_alignment(TextAlignment::CENTER),
+            ..default()
+        })
+        .insert(DistanceLabel { rect });
+}
+
+// Regions that are not rects, a circle and polygons
+fn add_other_regions(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let radius = 60.0;
+    commands
+        .spawn_bundle(MaterialMesh2dBundle {
+            mesh: meshes.add(shape::Circle::new(radius).into()).into(),
+            material: materials.add(ColorMaterial::from(Color::CRIMSON)),
+            transform: Transform::from_xyz(250.0, 150.0, 0.0),
+            ..default()
+        })
+        .insert(TriggerShape::Circle { radius });
+
+    let points = vec![
+        Vec2::new(-80.0, -40.0),
+        Vec2::new(60.0, -60.0),
+        Vec2::new(100.0, 20.0),
+        Vec2::new(0.0, 70.0),
+        Vec2::new(-90.0, 30.0),
+    ];
+    commands
+        .spawn_bundle(MaterialMesh2dBundle {
+            mesh: meshes.add(convex_polygon_mesh(&points)).into(),
+            material: materials.add(ColorMaterial::from(Color::CRIMSON)),
+            transform: Transform::from_xyz(0.0, -220.0, 0.0),
+            ..default()
+        })
+        .insert(TriggerShape::ConvexPolygon { points });
+
+    // A U shape, which is concave
+    let points = vec![
+        Vec2::new(-70.0, -70.0),
+        Vec2::new(70.0, -70.0),
+        Vec2::new(70.0, 70.0),
+        Vec2::new(30.0, 70.0),
+        Vec2::new(30.0, -25.0),
+        Vec2::new(-30.0, -25.0),
+        Vec2::new(-30.0, 70.0),
+        Vec2::new(-70.0, 70.0),
+    ];
+    add_polygon_region(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        Vec2::new(-400.0, -200.0),
+        points,
+        FillRule::NonZero,
+    );
+
+    // A star drawn in one line by going to every second corner of a pentagon, it crosses itself.
+    // With the even odd rule the pentagon in the middle is not part of it.
+    let points = (0..5)
+        .map(|i| Vec2::from_angle(i as f32 * 2.0 * TAU / 5.0 + TAU / 4.0) * 90.0)
+        .collect();
+    add_polygon_region(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        Vec2::new(400.0, -200.0),
+        points,
+        FillRule::EvenOdd,
+    );
+}
+
+// Polygons are drawn as outlines since the fill of a concave or crossing polygon is not a simple fan
+fn add_polygon_region(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    position: Vec2,
+    points: Vec<Vec2>,
+    fill_rule: FillRule,
+) {
+    commands
+        .spawn_bundle(MaterialMesh2dBundle {
+            mesh: meshes.add(polygon_outline_mesh(&points, 3.0)).into(),
+            material: materials.add(ColorMaterial::from(Color::CRIMSON)),
+            transform: Transform::from_translation(position.extend(0.0)),
+            ..default()
+        })
+        .insert(TriggerShape::Polygon { points, fill_rule });
 }
 
 fn add_moving_rectangle(mut commands: Commands) {
@@ -41,62 +188,210 @@ fn add_moving_rectangle(mut commands: Commands) {
                 color: Color::ORANGE,
                 ..Default::default()
             },
+            // Draw the player on top of the regions
+            transform: Transform::from_xyz(0.0, 0.0, 1.0),
             ..Default::default()
         })
-        .insert(Player { speed: 300.0 });
+        .insert(Player);
+}
+
+// Which test is used to check if the player is in a rect.
+// The axis aligned test ignores the rect rotation, the oriented one respects it.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+enum BoxMode {
+    #[default]
+    AxisAligned,
+    Oriented,
+}
+
+// Press M to switch between the axis aligned and the oriented box test.
+fn switch_box_mode(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut box_mode: ResMut<BoxMode>,
+    mut hud: ResMut<Hud>,
+) {
+    if keyboard_input.just_pressed(KeyCode::M) {
+        *box_mode = match *box_mode {
+            BoxMode::AxisAligned => BoxMode::Oriented,
+            BoxMode::Oriented => BoxMode::AxisAligned,
+        };
+    }
+
+    if box_mode.is_changed() {
+        hud.set("box mode", format!("{:?}", *box_mode));
+    }
 }
 
-fn move_player(
+// Rotate the rects with Q and E so there is something for the oriented test to respect.
+fn rotate_rectangle(
     keyboard_input: Res<Input<KeyCode>>,
-    mut player_query: Query<(&Player, &mut Transform)>,
+    mut rect_query: Query<&mut Transform, With<Rect>>,
     time: Res<Time>,
 ) {
-    if let Ok((player, mut transform)) = player_query.get_single_mut() {
-        // Get input from the keyboard (WASD)
-        let up: bool = keyboard_input.pressed(KeyCode::W) || keyboard_input.pressed(KeyCode::Up);
-        let down: bool =
-            keyboard_input.pressed(KeyCode::S) || keyboard_input.pressed(KeyCode::Down);
-        let left: bool =
-            keyboard_input.pressed(KeyCode::A) || keyboard_input.pressed(KeyCode::Left);
-        let right: bool =
-            keyboard_input.pressed(KeyCode::D) || keyboard_input.pressed(KeyCode::Right);
-
-        // If left is pressed than it will be -1, right 1, both they cancel out.
-        let x_axis: i8 = -(left as i8) + right as i8;
-        let y_axis: i8 = -(down as i8) + up as i8;
-        let move_delta: Vec2 = Vec2::new(x_axis as f32, y_axis as f32);
-
-        // move the player
-        let delta_time = time.delta_seconds();
-        transform.translation.x += move_delta.x * player.speed * delta_time;
-        transform.translation.y += move_delta.y * player.speed * delta_time;
+    let mut rotation_factor = 0.0;
+
+    if keyboard_input.pressed(KeyCode::Q) {
+        rotation_factor += 1.0;
+    }
+
+    if keyboard_input.pressed(KeyCode::E) {
+        rotation_factor -= 1.0;
+    }
+
+    // 90 degrees per second
+    let rotation_speed = f32::to_radians(90.0);
+    for mut transform in rect_query.iter_mut() {
+        transform.rotate(Quat::from_rotation_z(
+            rotation_factor * rotation_speed * time.delta_seconds(),
+        ));
+    }
+}
+
+// Keep the trigger shape of every rect in sync with the box mode and its sprite size.
+// The transform scale is applied by the trigger shape itself.
+fn update_rect_trigger_shapes(
+    box_mode: Res<BoxMode>,
+    mut rect_query: Query<(&Sprite, &mut TriggerShape), With<Rect>>,
+) {
+    for (sprite, mut shape) in rect_query.iter_mut() {
+        // Without a custom size the sprite is as big as its image, which we don't know here
+        let half_extents = match sprite.custom_size {
+            Some(size) => size / 2.0,
+            None => continue,
+        };
+
+        *shape = match *box_mode {
+            BoxMode::AxisAligned => TriggerShape::Aabb { half_extents },
+            BoxMode::Oriented => TriggerShape::Obb { half_extents },
+        };
     }
 }
 
-// Do some vector math to determine whether one object is inside another
-fn check_if_player_in_square(
-    player_query: Query<&Transform, (With<Player>, Without<Rect>)>,
-    mut rect_query: Query<(&Transform, &mut Sprite), With<Rect>>,
+// Color a region CYAN while any player is inside it and CRIMSON otherwise.
+// Regions are either sprites or meshes with a color material. Rects are colored by their distance instead.
+fn color_trigger_regions(
+    mut entered_events: EventReader<TriggerEntered>,
+    mut exited_events: EventReader<TriggerExited>,
+    mut players_inside: Local<HashMap<Entity, usize>>,
+    mut sprite_query: Query<&mut Sprite, Without<Rect>>,
+    material_query: Query<&Handle<ColorMaterial>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    let player_transform = player_query
-        .get_single()
-        .expect("Error: Could not find a single player.");
+    let mut changed_regions = Vec::new();
 
-    let (rect_transform, mut rect_sprite) = rect_query
-        .get_single_mut()
-        .expect("Error: Could not find a single rect.");
+    for event in entered_events.iter() {
+        *players_inside.entry(event.region).or_insert(0) += 1;
+        changed_regions.push(event.region);
+    }
 
-    // Get the vector from the player to the rectangle
-    let dist_p_to_r_vec: Vec3 = player_transform.translation - rect_transform.translation;
+    for event in exited_events.iter() {
+        if let Some(count) = players_inside.get_mut(&event.region) {
+            *count = count.saturating_sub(1);
+        }
+        changed_regions.push(event.region);
+    }
 
-    // Do the pythagorean theorum to get the magnitude or length of the vector
-    let magnitude_dist = f32::sqrt(dist_p_to_r_vec.x.powf(2.0) + dist_p_to_r_vec.y.powf(2.0)).abs();
+    for region in changed_regions {
+        let color = if players_inside.get(&region).copied().unwrap_or(0) > 0 {
+            Color::CYAN
+        } else {
+            Color::CRIMSON
+        };
 
-    // If the length of the vector is less than the the side of the square / 2, its inside the square
-    // 100 / 2 for side of rect, really should be square
-    if magnitude_dist < 50.0 {
-        rect_sprite.color = Color::CYAN;
+        if let Ok(mut sprite) = sprite_query.get_mut(region) {
+            sprite.color = color;
+        } else if let Ok(handle) = material_query.get(region) {
+            if let Some(material) = materials.get_mut(handle) {
+                material.color = color;
+            }
+        }
+    }
+}
+
+// Past this distance the rect color stops changing
+const COLOR_RAMP_DISTANCE: f32 = 200.0;
+
+// Inside the rect the color goes from CYAN at the border to WHITE deep inside,
+// outside it goes from CRIMSON at the border to almost black far away.
+fn distance_color(distance: f32) -> Color {
+    let t = (distance.abs() / COLOR_RAMP_DISTANCE).min(1.0);
+    let (near, far) = if distance <= 0.0 {
+        (Color::CYAN, Color::WHITE)
     } else {
-        rect_sprite.color = Color::CRIMSON;
+        (Color::CRIMSON, Color::rgb(0.1, 0.1, 0.1))
+    };
+
+    let near = Vec3::new(near.r(), near.g(), near.b());
+    let far = Vec3::new(far.r(), far.g(), far.b());
+    let color = near.lerp(far, t);
+    Color::rgb(color.x, color.y, color.z)
+}
+
+// Signed distance from the point to the rect, negative when the point is inside.
+// Like the trigger shapes the rotation is only used in the oriented box mode.
+fn signed_distance_to_rect(
+    point: Vec2,
+    rect_transform: &Transform,
+    half_extents: Vec2,
+    box_mode: BoxMode,
+) -> f32 {
+    let mut local_point = point - rect_transform.translation.truncate();
+    if box_mode == BoxMode::Oriented {
+        // Undo the rect rotation so the box is axis aligned around the origin
+        let local_x = rect_transform.local_x();
+        let rotation = local_x.y.atan2(local_x.x);
+        local_point = Vec2::from_angle(-rotation).rotate(local_point);
+    }
+    sdf_box(local_point, half_extents * rect_transform.scale.truncate())
+}
+
+// Tint every rect with the distance to the closest player and write that distance above it.
+fn show_distance_to_rects(
+    box_mode: Res<BoxMode>,
+    player_query: Query<&Transform, (With<Player>, Without<Rect>, Without<DistanceLabel>)>,
+    mut rect_query: Query<(&Transform, &mut Sprite), With<Rect>>,
+    mut label_query: Query<(&DistanceLabel, &mut Text, &mut Transform), Without<Rect>>,
+    mut debug_draw: ResMut<DebugDraw>,
+) {
+    for (label, mut text, mut label_transform) in label_query.iter_mut() {
+        let (rect_transform, mut rect_sprite) = match rect_query.get_mut(label.rect) {
+            Ok(rect) => rect,
+            Err(_) => continue,
+        };
+
+        let half_extents = match rect_sprite.custom_size {
+            Some(size) => size / 2.0,
+            None => continue,
+        };
+
+        // The vector from the middle of the rect to each player
+        for player_transform in player_query.iter() {
+            debug_draw.arrow(
+                rect_transform.translation.truncate(),
+                player_transform.translation.truncate(),
+                Color::WHITE,
+            );
+        }
+
+        let distance = player_query
+            .iter()
+            .map(|player_transform| {
+                signed_distance_to_rect(
+                    player_transform.translation.truncate(),
+                    rect_transform,
+                    half_extents,
+                    *box_mode,
+                )
+            })
+            .fold(f32::INFINITY, f32::min);
+
+        if distance.is_finite() {
+            rect_sprite.color = distance_color(distance);
+            text.sections[0].value = format!("{:.1}", distance);
+        }
+
+        // Keep the label just above the rect, drawn on top of everything
+        let above = half_extents.max_element() * rect_transform.scale.max_element() + 20.0;
+        label_transform.translation = rect_transform.translation + Vec3::new(0.0, above, 2.0);
     }
 }
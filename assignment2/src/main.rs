@@ -1,16 +1,32 @@
-use bevy::prelude::*;
-
-
-// TODO come back when you can draw a vector that rotates across the screen for easier visualization
-// But still, I got it right
+use bevy::{
+    prelude::*,
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+};
+use mathcore::containment::point_in_view_cone;
+use mathcore::debug_draw::{DebugDraw, DebugDrawPlugin};
+use mathcore::hud::{Hud, HudPlugin};
+use mathcore::mesh::wedge_mesh;
+use mathcore::player::{Player, PlayerControllerPlugin};
 
 fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
+        .add_plugin(DebugDrawPlugin)
+        .add_plugin(HudPlugin::default())
         .add_startup_system(show_origin)
         .add_startup_system(add_rectangle)
         .add_startup_system(add_moving_rectangle)
-        .add_system(move_player)
+        .add_plugin(PlayerControllerPlugin {
+            speed: 300.0,
+            // 180 degrees per second
+            rotation_speed: Some(f32::to_radians(180.0)),
+        })
+        .insert_resource(ViewCone {
+            half_angle: f32::to_radians(30.0),
+            max_range: 250.0,
+        })
+        .add_system(change_view_cone)
+        .add_system(update_view_cone_mesh)
         .add_system(calculate_if_player_facing_rect)
         .run();
 }
@@ -35,25 +51,39 @@ fn show_origin(mut commands: Commands) {
 struct Rect;
 
 fn add_rectangle(mut commands: Commands) {
-    commands
-        .spawn_bundle(SpriteBundle {
-            sprite: Sprite {
-                custom_size: Some(Vec2::new(10.0, 10.0)),
+    let positions = [
+        Vec3::new(-50.0, 100.0, 0.0),
+        Vec3::new(200.0, 150.0, 0.0),
+        Vec3::new(-250.0, -120.0, 0.0),
+        Vec3::new(300.0, -200.0, 0.0),
+    ];
+
+    for position in positions {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(Vec2::new(10.0, 10.0)),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(position),
                 ..Default::default()
-            },
-            transform: Transform::from_translation(Vec3::new(-50.0, 100.0, 0.0)),
-            ..Default::default()
-        })
-        .insert(Rect);
+            })
+            .insert(Rect);
+    }
 }
 
-// The float value is the player movement speed in 'pixels/second'.
-#[derive(Component)]
-struct Player {
-    pub speed: f32,
+// What the player can see. Everything at most `max_range` away and at most `half_angle`
+// radians to either side of where the player is facing.
+struct ViewCone {
+    half_angle: f32,
+    max_range: f32,
 }
 
-fn add_moving_rectangle(mut commands: Commands) {
+// The translucent wedge showing the view cone, it is a child of the player so it turns with it.
+#[derive(Component)]
+struct ViewConeMesh;
+
+fn add_moving_rectangle(mut commands: Commands, mut materials: ResMut<Assets<ColorMaterial>>) {
     commands
         .spawn_bundle(SpriteBundle {
             sprite: Sprite {
@@ -64,57 +94,121 @@ fn add_moving_rectangle(mut commands: Commands) {
             transform: Transform::from_translation(Vec3::new(120.0, 0.0, 0.0)),
             ..Default::default()
         })
-        .insert(Player { speed: 300.0 });
+        .insert(Player)
+        .with_children(|player| {
+            // Behind the player so the player stays visible
+            player
+                .spawn_bundle(MaterialMesh2dBundle {
+                    material: materials
+                        .add(ColorMaterial::from(Color::rgba(1.0, 1.0, 0.0, 0.2))),
+                    transform: Transform::from_xyz(0.0, 0.0, -0.5),
+                    ..default()
+                })
+                .insert(ViewConeMesh);
+        });
 }
 
-fn move_player(
+// Make the cone wider with Q and narrower with E, longer with R and shorter with F
+fn change_view_cone(
     keyboard_input: Res<Input<KeyCode>>,
-    mut player_query: Query<(&Player, &mut Transform)>,
+    mut view_cone: ResMut<ViewCone>,
     time: Res<Time>,
 ) {
-    if let Ok((player, mut transform)) = player_query.get_single_mut() {
-        // Get input from the keyboard (WASD)
-        let up: bool = keyboard_input.pressed(KeyCode::W) || keyboard_input.pressed(KeyCode::Up);
-        let down: bool =
-            keyboard_input.pressed(KeyCode::S) || keyboard_input.pressed(KeyCode::Down);
-        let left: bool =
-            keyboard_input.pressed(KeyCode::A) || keyboard_input.pressed(KeyCode::Left);
-        let right: bool =
-            keyboard_input.pressed(KeyCode::D) || keyboard_input.pressed(KeyCode::Right);
-
-        // If left is pressed than it will be -1, right 1, both they cancel out.
-        let x_axis: i8 = -(left as i8) + right as i8;
-        let y_axis: i8 = -(down as i8) + up as i8;
-        let move_delta: Vec2 = Vec2::new(x_axis as f32, y_axis as f32);
-
-        // move the player
-        let delta_time = time.delta_seconds();
-        transform.translation.x += move_delta.x * player.speed * delta_time;
-        transform.translation.y += move_delta.y * player.speed * delta_time;
+    let delta_time = time.delta_seconds();
+
+    let mut widen = 0.0;
+    if keyboard_input.pressed(KeyCode::Q) {
+        widen += 1.0;
+    }
+    if keyboard_input.pressed(KeyCode::E) {
+        widen -= 1.0;
+    }
+
+    let mut lengthen = 0.0;
+    if keyboard_input.pressed(KeyCode::R) {
+        lengthen += 1.0;
+    }
+    if keyboard_input.pressed(KeyCode::F) {
+        lengthen -= 1.0;
+    }
+
+    if widen != 0.0 || lengthen != 0.0 {
+        // Past 180 degrees the whole circle is covered
+        let half_angle = view_cone.half_angle + widen * f32::to_radians(45.0) * delta_time;
+        view_cone.half_angle = half_angle.clamp(0.0, std::f32::consts::PI);
+        view_cone.max_range = (view_cone.max_range + lengthen * 200.0 * delta_time).max(0.0);
+    }
+}
+
+// Rebuild the wedge when the view cone changes
+fn update_view_cone_mesh(
+    view_cone: Res<ViewCone>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut cone_query: Query<&mut Mesh2dHandle, With<ViewConeMesh>>,
+) {
+    if !view_cone.is_changed() {
+        return;
+    }
+
+    for mut mesh_handle in cone_query.iter_mut() {
+        let mesh = wedge_mesh(view_cone.max_range, view_cone.half_angle, 32);
+        mesh_handle.0 = meshes.add(mesh);
     }
 }
 
+// The player faces along its local y axis, which turns with the player's rotation.
+// The dot product of the normalized heading and the normalized direction from the player to a rect
+// is the cosine of the angle between them, 1 when the player looks straight at it and -1 when
+// it is right behind. Rects inside the view cone get highlighted.
 fn calculate_if_player_facing_rect(
+    view_cone: Res<ViewCone>,
     player_query: Query<&Transform, (With<Player>, Without<Rect>)>,
     mut rect_query: Query<(&Transform, &mut Sprite), With<Rect>>,
+    mut debug_draw: ResMut<DebugDraw>,
+    mut hud: ResMut<Hud>,
 ) {
     let player_transform = player_query
         .get_single()
         .expect("Error: Could not find a single player.");
 
-    let (rect_transform, mut rect_sprite) = rect_query
-        .get_single_mut()
-        .expect("Error: Could not find a single rect.");
-
-    // Make sure both vectors are normalized so the dot product is between 0 and 1/-1.
-    let normalized_player_translation = player_transform.translation.normalize_or_zero();
-    let normalized_rect_translation = rect_transform.translation.normalize_or_zero();
-
-    let dot_product = normalized_player_translation.x * normalized_rect_translation.x
-        + normalized_player_translation.y * normalized_rect_translation.y;
+    let player_position = player_transform.translation.truncate();
+    let heading = player_transform.local_y().truncate();
+    debug_draw.ray(player_position, heading * 60.0, Color::YELLOW);
+
+    // The rect the player is facing the most, it has the biggest dot product
+    let mut best_dot_product = -1.0;
+    let mut rects_in_view = 0;
+
+    for (rect_transform, mut rect_sprite) in rect_query.iter_mut() {
+        let rect_position = rect_transform.translation.truncate();
+
+        // Make sure both vectors are normalized so the dot product is between 1 and -1.
+        let player_to_rect = (rect_position - player_position).normalize_or_zero();
+        let dot_product = heading.normalize_or_zero().dot(player_to_rect);
+        best_dot_product = f32::max(best_dot_product, dot_product);
+
+        if point_in_view_cone(
+            rect_position,
+            player_position,
+            heading,
+            view_cone.half_angle,
+            view_cone.max_range,
+        ) {
+            rect_sprite.color = Color::GOLD;
+            rects_in_view += 1;
+            debug_draw.arrow(player_position, rect_position, Color::GOLD);
+        } else {
+            // Rect color gets darker the less the player faces the rect
+            // And the rect color gets bright the more the player faces the rect
+            let brightness = (dot_product + 1.0) / 4.0;
+            rect_sprite.color = Color::rgb(brightness, brightness, brightness);
+            debug_draw.arrow(player_position, rect_position, Color::DARK_GRAY);
+        }
+    }
 
-    // Rect color gets darker the less the player faces the rect
-    // And the rect color gets bright the more the player faces the rect
-    println!("Dot product is {}", dot_product);
-    rect_sprite.color = Color::rgb(dot_product, dot_product, dot_product);
+    hud.set("heading", heading);
+    hud.set("dot product", best_dot_product);
+    // The dot product is the cosine of the angle, so acos gives the angle back
+    hud.set("angle", best_dot_product.clamp(-1.0, 1.0).acos().to_degrees());
+    hud.set("rects in view", rects_in_view.to_string());
 }
@@ -1,24 +1,51 @@
 use bevy::prelude::*;
+use bevy::sprite::Anchor;
+use bevy::transform::TransformSystem;
+use mathcore::cursor::cursor_world_position;
+use mathcore::debug_draw::{DebugDraw, DebugDrawPlugin, DebugDrawSystem};
+use mathcore::hud::{Hud, HudPlugin};
+use mathcore::ik::{fabrik, two_bone_ik, BendDirection, JointLimit};
+use mathcore::matrix::Matrix3;
+use mathcore::player::{Player, PlayerControllerPlugin};
 
 // NOTE I think I am going to put this on hold for now. I think I got the general concept,
 // and I think I am wasting more time on this than it is really worth.
 // Perhaps I should come back after I learn more about rotation.
 
-
 fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
+        .add_plugin(DebugDrawPlugin)
+        .add_plugin(HudPlugin::default())
         .add_startup_system(setup)
         .add_startup_system(add_player)
-        .add_system(move_player)
-        .add_system(rotate_player)
+        .add_plugin(PlayerControllerPlugin {
+            speed: 300.0,
+            // 360 degrees per second
+            rotation_speed: Some(f32::to_radians(360.0)),
+        })
         .add_system(local_to_global_transform)
+        .add_system(place_rect_at_cursor)
+        .insert_resource(RobotArm {
+            segments: 4,
+            segment_length: 80.0,
+            base: Vec2::new(-300.0, -200.0),
+        })
+        .add_startup_system(add_robot_arm)
+        .add_system(rotate_arm_segments)
+        .insert_resource(IkMode::Off)
+        .add_system(change_ik_mode)
+        .add_system(reach_for_cursor.after(rotate_arm_segments))
+        // After Bevy worked out the GlobalTransforms for this frame, so both are compared on the same frame
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            show_arm_joints
+                .after(TransformSystem::TransformPropagate)
+                .before(DebugDrawSystem),
+        )
         .run();
 }
 
-#[derive(Component)]
-struct LocalToGlobalRect;
-
 // Show origin of the screen for easier visualization
 fn setup(mut commands: Commands) {
     commands.spawn_bundle(Camera2dBundle::default());
@@ -30,28 +57,11 @@ fn setup(mut commands: Commands) {
         },
         ..Default::default()
     });
-
-    commands
-        .spawn_bundle(SpriteBundle {
-            sprite: Sprite {
-                custom_size: Some(Vec2::new(1.0, 1.0)),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(LocalToGlobalRect);
 }
 
 #[derive(Component)]
 struct Rect;
 
-// The float value is the player movement speed in 'pixels/second'.
-#[derive(Component)]
-struct Player {
-    speed: f32,
-    rotation_speed: f32,
-}
-
 fn add_player(mut commands: Commands) {
     commands
         .spawn_bundle(SpriteBundle {
@@ -63,11 +73,7 @@ fn add_player(mut commands: Commands) {
             transform: Transform::from_translation(Vec3::new(120.0, 0.0, 0.0)),
             ..Default::default()
         })
-        .insert(Player {
-            speed: 300.0,
-            // degrees per second
-            rotation_speed: f32::to_radians(360.0),
-        })
+        .insert(Player)
         .with_children(|player| {
             // child cube
             player
@@ -83,95 +89,507 @@ fn add_player(mut commands: Commands) {
         });
 }
 
-fn move_player(
+// A chain of segments where each one is the child of the one before it,
+// so turning a segment turns every segment after it too.
+struct RobotArm {
+    // At most one per pair of keys in ARM_KEYS
+    segments: usize,
+    segment_length: f32,
+    // Where the first joint is in the world
+    base: Vec2,
+}
+
+// Segment i turns left with the first key and right with the second key
+const ARM_KEYS: [(KeyCode, KeyCode); 6] = [
+    (KeyCode::Z, KeyCode::X),
+    (KeyCode::C, KeyCode::V),
+    (KeyCode::B, KeyCode::N),
+    (KeyCode::T, KeyCode::Y),
+    (KeyCode::G, KeyCode::H),
+    (KeyCode::U, KeyCode::I),
+];
+
+// 90 degrees per second
+const ARM_ROTATION_SPEED: f32 = std::f32::consts::FRAC_PI_2;
+
+// The joint of a segment is at its origin and the segment points up its local y axis.
+#[derive(Component)]
+struct ArmSegment {
+    index: usize,
+    length: f32,
+}
+
+fn add_robot_arm(mut commands: Commands, arm: Res<RobotArm>) {
+    let colors = [Color::TEAL, Color::PURPLE, Color::OLIVE, Color::MAROON];
+    let mut parent: Option<Entity> = None;
+
+    for index in 0..arm.segments.min(ARM_KEYS.len()) {
+        // The first segment sits on the base, every other one at the end of the segment before it
+        let translation = match parent {
+            Some(_) => Vec3::new(0.0, arm.segment_length, 0.1),
+            None => arm.base.extend(0.0),
+        };
+
+        let segment = commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(Vec2::new(12.0, arm.segment_length)),
+                    color: colors[index % colors.len()],
+                    // So the sprite starts at the joint instead of being centered on it
+                    anchor: Anchor::BottomCenter,
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(translation),
+                ..Default::default()
+            })
+            .insert(ArmSegment {
+                index,
+                length: arm.segment_length,
+            })
+            .id();
+
+        if let Some(parent) = parent {
+            commands.entity(parent).add_child(segment);
+        }
+        parent = Some(segment);
+    }
+}
+
+fn rotate_arm_segments(
     keyboard_input: Res<Input<KeyCode>>,
-    mut player_query: Query<(&Player, &mut Transform)>,
+    mut segment_query: Query<(&ArmSegment, &mut Transform)>,
     time: Res<Time>,
 ) {
-    let (player, mut transform) = player_query
-        .get_single_mut()
-        .expect("Could not find single player");
+    for (segment, mut transform) in segment_query.iter_mut() {
+        let (left, right) = ARM_KEYS[segment.index];
+        let mut rotation_factor = 0.0;
+        if keyboard_input.pressed(left) {
+            rotation_factor += 1.0;
+        }
+        if keyboard_input.pressed(right) {
+            rotation_factor -= 1.0;
+        }
+        transform.rotate_z(rotation_factor * ARM_ROTATION_SPEED * time.delta_seconds());
+    }
+}
+
+// How the arm follows the cursor, F switches between them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IkMode {
+    Off,
+    // Every segment bends
+    Fabrik,
+    // The first segment is the upper bone and the rest of the arm is held straight as the lower bone
+    TwoBone,
+}
 
-    // Get input from the keyboard (WASD)
-    let up: bool = keyboard_input.pressed(KeyCode::W) || keyboard_input.pressed(KeyCode::Up);
-    let down: bool = keyboard_input.pressed(KeyCode::S) || keyboard_input.pressed(KeyCode::Down);
-    let left: bool = keyboard_input.pressed(KeyCode::A) || keyboard_input.pressed(KeyCode::Left);
-    let right: bool = keyboard_input.pressed(KeyCode::D) || keyboard_input.pressed(KeyCode::Right);
+// Every joint after the first bends at most this far to either side, about 143 degrees
+const ARM_BEND_LIMIT: f32 = 2.5;
 
-    // If left is pressed than it will be -1, right 1, both they cancel out.
-    let x_axis: i8 = -(left as i8) + right as i8;
-    let y_axis: i8 = -(down as i8) + up as i8;
-    let move_delta: Vec2 = Vec2::new(x_axis as f32, y_axis as f32);
+fn change_ik_mode(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut ik_mode: ResMut<IkMode>,
+    mut hud: ResMut<Hud>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F) {
+        *ik_mode = match *ik_mode {
+            IkMode::Off => IkMode::Fabrik,
+            IkMode::Fabrik => IkMode::TwoBone,
+            IkMode::TwoBone => IkMode::Off,
+        };
+    }
 
-    // move the player
-    let delta_time = time.delta_seconds();
-    transform.translation.x += move_delta.x * player.speed * delta_time;
-    transform.translation.y += move_delta.y * player.speed * delta_time;
+    if ik_mode.is_changed() {
+        hud.set("ik mode", format!("{:?}", *ik_mode));
+    }
 }
 
-fn rotate_player(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut player_query: Query<(&Player, &mut Transform)>,
-    time: Res<Time>,
+// Turn the arm segments so the tip of the arm ends up on the cursor.
+// The first segment has no parent, so its angle is measured from the world y axis,
+// the direction a segment points when it isn't rotated.
+fn reach_for_cursor(
+    ik_mode: Res<IkMode>,
+    arm: Res<RobotArm>,
+    windows: Res<Windows>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    mut segment_query: Query<(&ArmSegment, &mut Transform)>,
+    mut debug_draw: ResMut<DebugDraw>,
+    mut hud: ResMut<Hud>,
 ) {
-    let (player, mut transform) = player_query
-        .get_single_mut()
-        .expect("Could not find a single player");
+    if *ik_mode == IkMode::Off {
+        return;
+    }
 
-    let mut rotation_factor = 0.0;
+    let (camera, camera_transform) = camera_query
+        .get_single()
+        .expect("Could not find a single camera");
+    let target = match cursor_world_position(&windows, camera, camera_transform) {
+        Some(target) => target,
+        None => return,
+    };
+    debug_draw.circle(target, 8.0, Color::YELLOW);
+
+    let mut segments: Vec<_> = segment_query.iter_mut().collect();
+    segments.sort_by_key(|(segment, _)| segment.index);
+    let lengths: Vec<f32> = segments.iter().map(|(segment, _)| segment.length).collect();
+    let current_angles: Vec<f32> = segments
+        .iter()
+        .map(|(_, transform)| {
+            let x_axis = transform.rotation * Vec3::X;
+            x_axis.y.atan2(x_axis.x)
+        })
+        .collect();
+    let limits: Vec<JointLimit> = (0..segments.len())
+        .map(|index| {
+            if index == 0 {
+                JointLimit::FREE
+            } else {
+                JointLimit::symmetric(ARM_BEND_LIMIT)
+            }
+        })
+        .collect();
+
+    let angles = match *ik_mode {
+        IkMode::Off => return,
+        IkMode::Fabrik => {
+            let solution = fabrik(
+                arm.base,
+                Vec2::Y,
+                &lengths,
+                &current_angles,
+                &limits,
+                target,
+                0.1,
+                20,
+            );
+            hud.set("ik iterations", solution.iterations.to_string());
+            hud.set("ik distance", solution.distance);
+            solution.angles
+        }
+        IkMode::TwoBone => {
+            if lengths.len() < 2 {
+                return;
+            }
+            let upper = lengths[0];
+            let lower = lengths[1..].iter().sum();
+            let [first, second] = two_bone_ik(
+                arm.base,
+                Vec2::Y,
+                [upper, lower],
+                target,
+                BendDirection::Clockwise,
+                [limits[0], limits[1]],
+            );
+            let mut angles = vec![0.0; lengths.len()];
+            angles[0] = first;
+            angles[1] = second;
+            angles
+        }
+    };
+
+    for ((_, transform), angle) in segments.iter_mut().zip(angles) {
+        transform.rotation = Quat::from_rotation_z(angle);
+    }
+}
+
+// Local to world for any entity, by walking up the parents and multiplying their matrices on the left.
+// The parent's matrix goes on the left because it is applied after the child's.
+fn world_matrix(entity: Entity, transform_query: &Query<(&Transform, Option<&Parent>)>) -> Matrix3 {
+    let mut matrix = Matrix3::IDENTITY;
+    let mut current = Some(entity);
+    while let Some(entity) = current {
+        let (transform, parent) = match transform_query.get(entity) {
+            Ok(found) => found,
+            Err(_) => break,
+        };
+        matrix = Matrix3::from_transform(transform) * matrix;
+        current = parent.map(|parent| parent.get());
+    }
+    matrix
+}
+
+// Joints further apart than this from Bevy's GlobalTransform get flagged
+const JOINT_TOLERANCE: f32 = 0.01;
 
-    if keyboard_input.pressed(KeyCode::J) {
-        rotation_factor += 1.0;
+// Draw every joint where the hand rolled traversal puts it, and Bevy's answer next to it.
+fn show_arm_joints(
+    segment_query: Query<(Entity, &ArmSegment, &GlobalTransform)>,
+    transform_query: Query<(&Transform, Option<&Parent>)>,
+    mut debug_draw: ResMut<DebugDraw>,
+    mut hud: ResMut<Hud>,
+) {
+    let mut largest_divergence: f32 = 0.0;
+
+    for (entity, segment, global_transform) in segment_query.iter() {
+        let matrix = world_matrix(entity, &transform_query);
+        let joint = matrix.translation();
+        let bevy_joint = global_transform.translation().truncate();
+        let divergence = joint.distance(bevy_joint);
+        largest_divergence = largest_divergence.max(divergence);
+
+        debug_draw.circle(bevy_joint, 10.0, Color::GREEN);
+        if divergence > JOINT_TOLERANCE {
+            debug_draw.circle(joint, 6.0, Color::RED);
+            debug_draw.text_label(
+                joint + Vec2::new(0.0, -25.0),
+                format!("off by {:.2}", divergence),
+                Color::RED,
+            );
+        } else {
+            debug_draw.circle(joint, 6.0, Color::WHITE);
+        }
+        debug_draw.text_label(
+            joint + Vec2::new(60.0, 0.0),
+            format!("{}: ({:.1}, {:.1})", segment.index, joint.x, joint.y),
+            Color::WHITE,
+        );
+
+        // The tip of the last segment is not a joint of any segment, so show it on its own
+        if segment_query
+            .iter()
+            .all(|(_, other, _)| other.index <= segment.index)
+        {
+            let tip = matrix.transform_point(Vec2::new(0.0, segment.length));
+            debug_draw.circle(tip, 6.0, Color::YELLOW);
+            hud.set("arm tip", tip);
+        }
     }
 
-    if keyboard_input.pressed(KeyCode::K) {
-        rotation_factor -= 1.0;
+    hud.set("arm divergence", largest_divergence);
+}
+
+// Map a position that is local to `parent` into world space.
+// The parent's transform as a matrix scales, then rotates, then translates,
+// so multiplying the local position by it walks along the parent's stretched and turned axes.
+fn local_to_global_position(parent: &Transform, local_position: Vec2) -> Vec2 {
+    Matrix3::from_transform(parent).transform_point(local_position)
+}
+
+// The inverse of `local_to_global_position`, map a world space position into the local space of `parent`.
+// A parent scaled to 0 on an axis squashes everything onto a line, so there is no way back.
+fn global_to_local_position(parent: &Transform, global_position: Vec2) -> Option<Vec2> {
+    let inverse = Matrix3::from_transform(parent).inverse()?;
+    Some(inverse.transform_point(global_position))
+}
+
+// Clicking moves the child rect under the cursor. The rect stays a child of the player,
+// so the click position has to be turned into the player's local space first.
+fn place_rect_at_cursor(
+    mouse_input: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    player_query: Query<&Transform, (With<Player>, Without<Rect>)>,
+    mut rect_query: Query<&mut Transform, With<Rect>>,
+    mut hud: ResMut<Hud>,
+) {
+    if !mouse_input.just_pressed(MouseButton::Left) {
+        return;
     }
 
-    // update the player rotation around the Z axis (perpendicular to the 2D plane of the screen)
-    let delta_time = time.delta_seconds();
-    transform.rotate(Quat::from_rotation_z(
-        rotation_factor * player.rotation_speed * delta_time,
-    ));
+    let (camera, camera_transform) = camera_query
+        .get_single()
+        .expect("Could not find a single camera");
+    let cursor_position = match cursor_world_position(&windows, camera, camera_transform) {
+        Some(cursor_position) => cursor_position,
+        None => return,
+    };
+
+    let player_transform = player_query
+        .get_single()
+        .expect("Could not find a single player");
+    let mut rect_transform = rect_query
+        .get_single_mut()
+        .expect("Could not find single rect");
+
+    let local_position = match global_to_local_position(player_transform, cursor_position) {
+        Some(local_position) => local_position,
+        None => return,
+    };
+    rect_transform.translation = local_position.extend(rect_transform.translation.z);
+
+    hud.set("clicked", cursor_position);
+    hud.set("rect local position", local_position);
 }
 
 // Find the global transform of the rectangle given the local transform from the player parent.
 // The default transform is the transform relative to its parent position, so its local for the rect
 fn local_to_global_transform(
-    rect_query: Query<&Transform, With<Rect>>,
+    rect_query: Query<(&Transform, &GlobalTransform), With<Rect>>,
     player_query: Query<&Transform, (With<Player>, Without<Rect>)>,
-    mut local_to_global_rect_query: Query<&mut Transform, (With<LocalToGlobalRect>, Without<Player>, Without<Rect>)>,
+    mut debug_draw: ResMut<DebugDraw>,
+    mut hud: ResMut<Hud>,
 ) {
-    let rect_local_transform = rect_query.get_single().expect("Could not find single rect");
+    let (rect_local_transform, rect_global_transform) =
+        rect_query.get_single().expect("Could not find single rect");
 
     let player_transform = player_query
         .get_single()
         .expect("Could not find a single player");
 
-    let mut origin_to_rect_transform = local_to_global_rect_query
-        .get_single_mut()
-        .expect("Cannot find global to local rect");
+    let rect_position = local_to_global_position(
+        player_transform,
+        rect_local_transform.translation.truncate(),
+    );
 
-    let x_vector = rect_local_transform.local_x() * rect_local_transform.translation.x;
-    let y_vector = rect_local_transform.local_y() * rect_local_transform.translation.y;
-    let rect_vector_from_player = x_vector + y_vector;
+    debug_draw.arrow(Vec2::ZERO, rect_position, Color::CYAN);
+    debug_draw.arrow(
+        player_transform.translation.truncate(),
+        rect_position,
+        Color::ORANGE,
+    );
+    debug_draw.text_label(
+        rect_position + Vec2::new(0.0, 20.0),
+        format!("({:.1}, {:.1})", rect_position.x, rect_position.y),
+        Color::WHITE,
+    );
+    hud.set("rect position", rect_position);
+    hud.set("magnitude", rect_position.length());
+    // Bevy's GlobalTransform is a frame behind while moving, so this is only 0 when standing still
+    hud.set(
+        "difference from GlobalTransform",
+        rect_position.distance(rect_global_transform.translation().truncate()),
+    );
+}
 
-    let rect_vector_from_origin = rect_vector_from_player + player_transform.translation;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::SystemState;
+    use bevy::hierarchy::HierarchyPlugin;
+    use bevy::transform::TransformPlugin;
 
-    // Put the rect at the midpoint because scaling a transform occures in the middle
-    origin_to_rect_transform.translation.x = rect_vector_from_origin.x / 2.0;
-    origin_to_rect_transform.translation.y = rect_vector_from_origin.y / 2.0;
+    const EPSILON: f32 = 1e-3;
 
-    // TODO
-    // I think something is wrong with the math, but I do think you are close.
-    // I think you first need to rotate the vector so that it is always facing the rect
-    let distance_vec_magnitude = origin_to_rect_transform.translation.length();
-    origin_to_rect_transform.scale.y = distance_vec_magnitude / 2.0;
+    #[test]
+    fn global_to_local_undoes_local_to_global() {
+        let parent = Transform {
+            translation: Vec3::new(120.0, -40.0, 0.0),
+            rotation: Quat::from_rotation_z(f32::to_radians(130.0)),
+            scale: Vec3::new(2.0, 0.5, 1.0),
+        };
 
-    origin_to_rect_transform.look_at(-Vec3::Z, Vec3::new(rect_vector_from_origin.x, rect_vector_from_origin.y, 0.0));
-    println!("Scale of the vector {}", origin_to_rect_transform.scale.y);
-}
+        for local_position in [
+            Vec2::new(-50.0, 100.0),
+            Vec2::new(0.0, 0.0),
+            Vec2::new(33.0, -7.5),
+        ] {
+            let global_position = local_to_global_position(&parent, local_position);
+            let back = global_to_local_position(&parent, global_position).unwrap();
+            assert!(
+                back.distance(local_position) < EPSILON,
+                "{} came back as {}",
+                local_position,
+                back
+            );
+        }
+
+        // The parent's own position is the local origin
+        let origin = global_to_local_position(&parent, parent.translation.truncate()).unwrap();
+        assert!(origin.length() < EPSILON);
 
-// TODO
-// 3. Draw a rect from the origin to the cube and
-//    show the global coordinates of the vector using vector math.
+        // Squashed flat, there is no way back
+        let flat = Transform::from_scale(Vec3::new(1.0, 0.0, 1.0));
+        assert_eq!(global_to_local_position(&flat, Vec2::ONE), None);
+    }
+
+    #[test]
+    fn local_to_global_matches_global_transform() {
+        let mut app = App::new();
+        app.add_plugin(TransformPlugin).add_plugin(HierarchyPlugin);
+
+        let child_translation = Vec3::new(-50.0, 100.0, 0.0);
+        let mut child = None;
+        let parent = app
+            .world
+            .spawn()
+            .insert_bundle(TransformBundle::default())
+            .with_children(|parent| {
+                child = Some(
+                    parent
+                        .spawn_bundle(TransformBundle::from_transform(
+                            Transform::from_translation(child_translation),
+                        ))
+                        .id(),
+                );
+            })
+            .id();
+        let child = child.unwrap();
+
+        // Spin the parent through two full turns while moving and stretching it
+        for step in 0..720 {
+            let angle = f32::to_radians(step as f32);
+            let parent_transform = Transform {
+                translation: Vec3::new(step as f32 - 360.0, 120.0, 0.0),
+                rotation: Quat::from_rotation_z(angle),
+                scale: Vec3::new(1.0 + step as f32 / 360.0, 0.5, 1.0),
+            };
+            *app.world.get_mut::<Transform>(parent).unwrap() = parent_transform;
+            app.update();
+
+            let expected = app
+                .world
+                .get::<GlobalTransform>(child)
+                .unwrap()
+                .translation()
+                .truncate();
+            let computed =
+                local_to_global_position(&parent_transform, child_translation.truncate());
+            assert!(
+                computed.distance(expected) < EPSILON,
+                "at {} degrees computed {} but bevy has {}",
+                step,
+                computed,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn arm_joints_match_global_transform() {
+        let mut app = App::new();
+        app.add_plugin(TransformPlugin)
+            .add_plugin(HierarchyPlugin)
+            .insert_resource(RobotArm {
+                segments: 5,
+                segment_length: 80.0,
+                base: Vec2::new(-300.0, -200.0),
+            })
+            .add_startup_system(add_robot_arm);
+        app.update();
+
+        let segments: Vec<Entity> = app
+            .world
+            .query_filtered::<Entity, With<ArmSegment>>()
+            .iter(&app.world)
+            .collect();
+        assert_eq!(segments.len(), 5);
+
+        for step in 0..100 {
+            // Turn every segment by a different amount
+            for (i, entity) in segments.iter().enumerate() {
+                let mut transform = app.world.get_mut::<Transform>(*entity).unwrap();
+                transform.rotate_z(0.01 * (i + 1) as f32 * step as f32);
+            }
+            app.update();
+
+            let mut state: SystemState<Query<(&Transform, Option<&Parent>)>> =
+                SystemState::new(&mut app.world);
+            let transform_query = state.get(&app.world);
+            for entity in segments.iter() {
+                let joint = world_matrix(*entity, &transform_query).translation();
+                let expected = app
+                    .world
+                    .get::<GlobalTransform>(*entity)
+                    .unwrap()
+                    .translation()
+                    .truncate();
+                assert!(
+                    joint.distance(expected) < JOINT_TOLERANCE,
+                    "computed {} but bevy has {}",
+                    joint,
+                    expected
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use crate::level::{Level, LevelHandle, Role};
+use crate::puzzle::PuzzleState;
+use bevy::prelude::*;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use mathcore::mesh::MeshBuffers;
+use mathcore::player::Player;
+use mathcore::visibility::{shadow_buffers, visibility_buffers, visibility_polygon};
+
+// Fog of war. Everything the player can't see from where it stands is darkened,
+// walls block the view and see through shapes and puzzle targets don't. V turns it on and off.
+pub struct FogPlugin {
+    // Half the size of the area that can be seen into, centered on the origin
+    pub half_size: Vec2,
+}
+
+impl Plugin for FogPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FogSettings {
+            enabled: true,
+            half_size: self.half_size,
+        })
+        .add_startup_system(add_fog)
+        .add_system(toggle_fog)
+        .add_system(update_fog);
+    }
+}
+
+pub struct FogSettings {
+    pub enabled: bool,
+    pub half_size: Vec2,
+}
+
+#[derive(Component)]
+enum FogMesh {
+    // Lightly brightens what can be seen
+    Lit,
+    // Darkens everything else
+    Shadow,
+}
+
+fn add_fog(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for (fog_mesh, color) in [
+        (FogMesh::Lit, Color::rgba(1.0, 1.0, 0.8, 0.05)),
+        (FogMesh::Shadow, Color::rgba(0.0, 0.0, 0.0, 0.8)),
+    ] {
+        commands
+            .spawn_bundle(MaterialMesh2dBundle {
+                mesh: meshes.add(MeshBuffers::new().into_mesh()).into(),
+                material: materials.add(ColorMaterial::from(color)),
+                // Over the level and the laser
+                transform: Transform::from_xyz(0.0, 0.0, 3.0),
+                ..default()
+            })
+            .insert(fog_mesh);
+    }
+}
+
+fn toggle_fog(keyboard_input: Res<Input<KeyCode>>, mut fog_settings: ResMut<FogSettings>) {
+    if keyboard_input.just_pressed(KeyCode::V) {
+        fog_settings.enabled = !fog_settings.enabled;
+    }
+}
+
+// The segments that block the view, the edges of the area and of every solid level shape.
+// `rotations` are the puzzle's, so turned mirrors cast their shadows where they are now.
+pub fn view_blockers(
+    half_size: Vec2,
+    level: Option<&Level>,
+    rotations: &[f32],
+) -> Vec<(Vec2, Vec2)> {
+    let corners = [
+        Vec2::new(-half_size.x, -half_size.y),
+        Vec2::new(half_size.x, -half_size.y),
+        Vec2::new(half_size.x, half_size.y),
+        Vec2::new(-half_size.x, half_size.y),
+    ];
+    let mut segments: Vec<(Vec2, Vec2)> =
+        (0..4).map(|i| (corners[i], corners[(i + 1) % 4])).collect();
+
+    if let Some(level) = level {
+        // Until the puzzle has caught up with a new level, the rotations are the ones in the file
+        let turned = rotations.len() == level.shapes.len();
+        for (index, shape) in level.shapes.iter().enumerate() {
+            if shape.optics.is_none() && shape.role != Role::Target {
+                let rotation = if turned {
+                    rotations[index]
+                } else {
+                    shape.rotation
+                };
+                segments.extend(shape.edges_at(rotation));
+            }
+        }
+    }
+    segments
+}
+
+fn update_fog(
+    fog_settings: Res<FogSettings>,
+    player_query: Query<&Transform, With<Player>>,
+    level_handle: Option<Res<LevelHandle>>,
+    levels: Res<Assets<Level>>,
+    puzzle_state: Option<Res<PuzzleState>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut fog_query: Query<(&FogMesh, &Mesh2dHandle, &mut Visibility)>,
+) {
+    let origin = match player_query.get_single() {
+        Ok(transform) if fog_settings.enabled => transform.translation.truncate(),
+        _ => {
+            for (_, _, mut visibility) in fog_query.iter_mut() {
+                visibility.is_visible = false;
+            }
+            return;
+        }
+    };
+
+    let level = level_handle.and_then(|level_handle| levels.get(&level_handle.0));
+    let rotations = match &puzzle_state {
+        Some(puzzle_state) => puzzle_state.rotations.as_slice(),
+        None => &[],
+    };
+    let segments = view_blockers(fog_settings.half_size, level, rotations);
+    // Far enough to reach every corner of the area from anywhere in it
+    let distance = fog_settings.half_size.length() * 2.0;
+    let polygon = visibility_polygon(origin, &segments, distance);
+
+    for (fog_mesh, mesh_handle, mut visibility) in fog_query.iter_mut() {
+        visibility.is_visible = true;
+        if let Some(mesh) = meshes.get_mut(&mesh_handle.0) {
+            let buffers = match fog_mesh {
+                FogMesh::Lit => visibility_buffers(origin, &polygon),
+                FogMesh::Shadow => shadow_buffers(origin, &polygon, distance),
+            };
+            *mesh = buffers.into_mesh();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::{arena_level, parse_level};
+    use mathcore::polygon::winding_number;
+    use std::path::Path;
+
+    #[test]
+    fn walls_in_the_arena_block_the_view() {
+        let level = arena_level();
+        let half_size = Vec2::new(600.0, 500.0);
+        let segments = view_blockers(half_size, Some(&level), &[]);
+
+        // Right below the bumper, a circle of radius 40 at (-300, 200)
+        let origin = Vec2::new(-300.0, 100.0);
+        let polygon = visibility_polygon(origin, &segments, half_size.length() * 2.0);
+        let seen = |point: Vec2| winding_number(point, &polygon) != 0;
+
+        assert!(seen(Vec2::new(-300.0, 150.0)));
+        assert!(!seen(Vec2::new(-300.0, 300.0)));
+        // The lens is see through, whatever is behind it isn't hidden
+        assert!(seen(Vec2::new(-100.0, -320.0)));
+    }
+
+    #[test]
+    fn turned_mirrors_move_their_shadow() {
+        let path = Path::new("assets/levels/puzzles/corner.level.ron");
+        let level = parse_level(&std::fs::read(path).unwrap(), path).unwrap();
+        let half_size = Vec2::new(600.0, 500.0);
+        let mirror = level
+            .shapes
+            .iter()
+            .position(|shape| shape.role == Role::Mirror)
+            .unwrap();
+
+        // Above the mirror at (300, -400), looking down past it
+        let origin = Vec2::new(300.0, -300.0);
+        let behind = Vec2::new(330.0, -460.0);
+        let seen = |rotations: &[f32]| {
+            let segments = view_blockers(half_size, Some(&level), rotations);
+            let polygon = visibility_polygon(origin, &segments, half_size.length() * 2.0);
+            winding_number(behind, &polygon) != 0
+        };
+
+        // As it is in the file the mirror lies almost flat and hides the floor below it
+        let mut rotations: Vec<f32> = level.shapes.iter().map(|shape| shape.rotation).collect();
+        assert!(!seen(&rotations));
+        assert!(!seen(&[]));
+
+        // Turned on its end it is out of the way
+        rotations[mirror] = 90.0;
+        assert!(seen(&rotations));
+    }
+}
@@ -0,0 +1,609 @@
+use bevy::asset::{AssetLoader, LoadContext, LoadState, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::sprite::MaterialMesh2dBundle;
+use bevy::utils::BoxedFuture;
+use bevy_rapier2d::prelude::*;
+use mathcore::hud::Hud;
+use mathcore::laser::RefractiveIndex;
+use mathcore::mesh::{convex_polygon_mesh, MeshBuffers};
+use mathcore::ray::{ray_circle, ray_convex_polygon, ray_obb, ray_segment, RayIntersection};
+use serde::Deserialize;
+use std::fmt;
+use std::path::Path;
+
+// Levels are files in assets/levels ending in .level.ron or .level.json, listing the shapes to spawn.
+// The level is loaded through the asset server, so with `watch_for_changes` on
+// saving the file swaps the whole level out while the game is running.
+// A file that can't be read is reported in the log and the HUD, and the last good level stays.
+pub struct LevelPlugin {
+    // Relative to the assets folder
+    pub path: String,
+}
+
+impl Plugin for LevelPlugin {
+    fn build(&self, app: &mut App) {
+        let path = self.path.clone();
+        app.add_asset::<Level>()
+            .init_asset_loader::<LevelLoader>()
+            .add_startup_system(
+                move |mut commands: Commands, asset_server: Res<AssetServer>| {
+                    commands.insert_resource(LevelHandle(asset_server.load(path.as_str())));
+                },
+            )
+            .add_system(spawn_level)
+            .add_system(show_level_state);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, TypeUuid)]
+#[uuid = "b227919d-108c-4638-a58d-0ebb7e3418fb"]
+pub struct Level {
+    pub shapes: Vec<LevelShape>,
+    // Puzzles have a fixed laser to aim at the targets
+    #[serde(default)]
+    pub emitter: Option<Emitter>,
+}
+
+// Where the puzzle laser comes from
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Emitter {
+    pub position: [f32; 2],
+    // Degrees counter clockwise from the x axis
+    pub direction: f32,
+    #[serde(default = "default_emitter_bounces")]
+    pub max_bounces: usize,
+    #[serde(default = "default_emitter_length")]
+    pub length: f32,
+}
+
+fn default_emitter_bounces() -> usize {
+    10
+}
+
+fn default_emitter_length() -> f32 {
+    5000.0
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct LevelShape {
+    // Only used in error messages
+    #[serde(default)]
+    pub name: String,
+    pub shape: ShapeKind,
+    #[serde(default)]
+    pub position: [f32; 2],
+    // Degrees counter clockwise
+    #[serde(default)]
+    pub rotation: f32,
+    // Red, green and blue from 0 to 1
+    #[serde(default = "default_color")]
+    pub color: [f32; 3],
+    #[serde(default)]
+    pub physics: Physics,
+    // Shapes with optics let the laser through and bend it
+    #[serde(default)]
+    pub optics: Option<Optics>,
+    #[serde(default)]
+    pub role: Role,
+}
+
+// What a shape does in a puzzle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum Role {
+    #[default]
+    Wall,
+    // Can be selected and turned by the player
+    Mirror,
+    // The puzzle laser goes through it and the puzzle is solved when it goes through all of them
+    Target,
+}
+
+// Sizes and points are in pixels, relative to the shape's position.
+// Pairs of numbers are written (x, y) in RON and [x, y] in JSON.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum ShapeKind {
+    Box { half_extents: [f32; 2] },
+    Circle { radius: f32 },
+    // A convex polygon, the points go around it in order
+    Polygon { points: Vec<[f32; 2]> },
+    // A wall with no thickness
+    Segment { start: [f32; 2], end: [f32; 2] },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Physics {
+    pub friction: f32,
+    // How bouncy, 0 doesn't bounce and 1 bounces back with all its speed
+    pub restitution: f32,
+    // Sensors report what touches them but don't block anything
+    pub sensor: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Optics {
+    // 1 is air, water is about 1.33 and glass about 1.5
+    pub refractive_index: f32,
+    // How much more blue light bends than red light, a prism needs this to split the beam
+    #[serde(default)]
+    pub dispersion: f32,
+}
+
+// The same as rapier's defaults
+impl Default for Physics {
+    fn default() -> Self {
+        Physics {
+            friction: 0.5,
+            restitution: 0.0,
+            sensor: false,
+        }
+    }
+}
+
+fn default_color() -> [f32; 3] {
+    [0.04, 0.27, 0.27]
+}
+
+// How thick segments are drawn, they have no thickness for physics
+const SEGMENT_WIDTH: f32 = 4.0;
+
+// How many straight edges a circle's outline is cut into
+const CIRCLE_EDGES: usize = 24;
+
+#[derive(Debug)]
+pub enum LevelError {
+    Ron(ron::Error),
+    Json(serde_json::Error),
+    UnknownFormat(String),
+    InvalidShape {
+        index: usize,
+        name: String,
+        reason: String,
+    },
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LevelError::Ron(error) => write!(f, "not a valid RON level: {}", error),
+            LevelError::Json(error) => write!(f, "not a valid JSON level: {}", error),
+            LevelError::UnknownFormat(path) => {
+                write!(f, "{} should end in .level.ron or .level.json", path)
+            }
+            LevelError::InvalidShape {
+                index,
+                name,
+                reason,
+            } => write!(f, "shape {} \"{}\": {}", index, name, reason),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+// Read a level from the contents of a file, the extension of `path` says which format it is in.
+pub fn parse_level(bytes: &[u8], path: &Path) -> Result<Level, LevelError> {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    let level: Level = match extension {
+        Some("ron") => ron::de::from_bytes(bytes).map_err(LevelError::Ron)?,
+        Some("json") => serde_json::from_slice(bytes).map_err(LevelError::Json)?,
+        _ => return Err(LevelError::UnknownFormat(path.display().to_string())),
+    };
+
+    for (index, shape) in level.shapes.iter().enumerate() {
+        let mut result = shape.check();
+        if shape.role == Role::Target && level.emitter.is_none() {
+            result = Err("a target needs an emitter in the level to aim at it".to_string());
+        }
+        result.map_err(|reason| LevelError::InvalidShape {
+            index,
+            name: shape.name.clone(),
+            reason,
+        })?;
+    }
+
+    Ok(level)
+}
+
+fn to_vec2(point: [f32; 2]) -> Vec2 {
+    Vec2::new(point[0], point[1])
+}
+
+impl LevelShape {
+    // Catch what rapier would panic on or silently get wrong
+    fn check(&self) -> Result<(), String> {
+        if let Some(optics) = self.optics {
+            if optics.refractive_index - optics.dispersion.abs() <= 0.0 {
+                return Err("a refractive index has to stay above 0 for every color".to_string());
+            }
+            if self.role != Role::Wall {
+                return Err("mirrors and targets can't be see through".to_string());
+            }
+        }
+
+        match &self.shape {
+            ShapeKind::Box { half_extents } => {
+                if half_extents[0] <= 0.0 || half_extents[1] <= 0.0 {
+                    return Err("a box needs a positive width and height".to_string());
+                }
+            }
+            ShapeKind::Circle { radius } => {
+                if *radius <= 0.0 {
+                    return Err("a circle needs a positive radius".to_string());
+                }
+            }
+            ShapeKind::Polygon { points } => {
+                if points.len() < 3 {
+                    return Err("a polygon needs at least 3 points".to_string());
+                }
+                // Every corner has to turn the same way
+                let points: Vec<Vec2> = points.iter().map(|point| to_vec2(*point)).collect();
+                let turns: Vec<f32> = (0..points.len())
+                    .map(|i| {
+                        let a = points[i];
+                        let b = points[(i + 1) % points.len()];
+                        let c = points[(i + 2) % points.len()];
+                        (b - a).perp_dot(c - b)
+                    })
+                    .collect();
+                if !(turns.iter().all(|turn| *turn > 0.0) || turns.iter().all(|turn| *turn < 0.0)) {
+                    return Err("a polygon has to be convex with its points in order".to_string());
+                }
+            }
+            ShapeKind::Segment { start, end } => {
+                if start == end {
+                    return Err("a segment needs two different ends".to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The outline as segments in world space, circles are cut into CIRCLE_EDGES straight edges.
+    // Segments are a single edge.
+    pub fn edges(&self) -> Vec<(Vec2, Vec2)> {
+        self.edges_at(self.rotation)
+    }
+
+    // The same outline when the shape is turned `rotation` degrees instead
+    pub fn edges_at(&self, rotation: f32) -> Vec<(Vec2, Vec2)> {
+        let points: Vec<Vec2> = match &self.shape {
+            ShapeKind::Box { half_extents } => {
+                let [x, y] = *half_extents;
+                vec![
+                    Vec2::new(-x, -y),
+                    Vec2::new(x, -y),
+                    Vec2::new(x, y),
+                    Vec2::new(-x, y),
+                ]
+            }
+            ShapeKind::Circle { radius } => (0..CIRCLE_EDGES)
+                .map(|i| {
+                    Vec2::from_angle(std::f32::consts::TAU * i as f32 / CIRCLE_EDGES as f32)
+                        * *radius
+                })
+                .collect(),
+            ShapeKind::Polygon { points } => points.iter().map(|point| to_vec2(*point)).collect(),
+            ShapeKind::Segment { start, end } => vec![to_vec2(*start), to_vec2(*end)],
+        };
+
+        let rotation = Vec2::from_angle(rotation.to_radians());
+        let position = to_vec2(self.position);
+        let points: Vec<Vec2> = points
+            .iter()
+            .map(|point| position + rotation.rotate(*point))
+            .collect();
+
+        if points.len() == 2 {
+            return vec![(points[0], points[1])];
+        }
+        (0..points.len())
+            .map(|i| (points[i], points[(i + 1) % points.len()]))
+            .collect()
+    }
+
+    // Where a ray first crosses the outline when the shape is turned `rotation` degrees,
+    // the same as rapier would answer without solid, so from inside it hits the far side.
+    pub fn cast_ray(
+        &self,
+        rotation: f32,
+        origin: Vec2,
+        direction: Vec2,
+        max_toi: f32,
+    ) -> Option<RayIntersection> {
+        let position = to_vec2(self.position);
+        let angle = rotation.to_radians();
+        let turn = Vec2::from_angle(angle);
+        match &self.shape {
+            ShapeKind::Box { half_extents } => ray_obb(
+                origin,
+                direction,
+                max_toi,
+                position,
+                to_vec2(*half_extents),
+                angle,
+                false,
+            ),
+            ShapeKind::Circle { radius } => {
+                ray_circle(origin, direction, max_toi, position, *radius, false)
+            }
+            ShapeKind::Polygon { points } => {
+                let points: Vec<Vec2> = points
+                    .iter()
+                    .map(|point| position + turn.rotate(to_vec2(*point)))
+                    .collect();
+                ray_convex_polygon(origin, direction, max_toi, &points, false)
+            }
+            ShapeKind::Segment { start, end } => ray_segment(
+                origin,
+                direction,
+                max_toi,
+                position + turn.rotate(to_vec2(*start)),
+                position + turn.rotate(to_vec2(*end)),
+            ),
+        }
+    }
+
+    fn collider(&self) -> Option<Collider> {
+        match &self.shape {
+            ShapeKind::Box { half_extents } => {
+                Some(Collider::cuboid(half_extents[0], half_extents[1]))
+            }
+            ShapeKind::Circle { radius } => Some(Collider::ball(*radius)),
+            ShapeKind::Polygon { points } => {
+                let points: Vec<Vec2> = points.iter().map(|point| to_vec2(*point)).collect();
+                Collider::convex_hull(&points)
+            }
+            ShapeKind::Segment { start, end } => {
+                Some(Collider::segment(to_vec2(*start), to_vec2(*end)))
+            }
+        }
+    }
+
+    fn mesh(&self) -> Mesh {
+        match &self.shape {
+            ShapeKind::Box { half_extents } => {
+                let [x, y] = *half_extents;
+                convex_polygon_mesh(&[
+                    Vec2::new(-x, -y),
+                    Vec2::new(x, -y),
+                    Vec2::new(x, y),
+                    Vec2::new(-x, y),
+                ])
+            }
+            ShapeKind::Circle { radius } => Mesh::from(shape::Circle::new(*radius)),
+            ShapeKind::Polygon { points } => {
+                let points: Vec<Vec2> = points.iter().map(|point| to_vec2(*point)).collect();
+                convex_polygon_mesh(&points)
+            }
+            ShapeKind::Segment { start, end } => {
+                let mut buffers = MeshBuffers::new();
+                buffers.push_line(to_vec2(*start), to_vec2(*end), SEGMENT_WIDTH);
+                buffers.into_mesh()
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let level = parse_level(bytes, load_context.path())?;
+            load_context.set_default_asset(LoadedAsset::new(level));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron", "level.json"]
+    }
+}
+
+pub struct LevelHandle(pub Handle<Level>);
+
+// Everything spawned from the level file, so it can be removed when the file changes.
+// `index` is which of the level's shapes it is.
+#[derive(Component)]
+pub struct LevelEntity {
+    pub index: usize,
+}
+
+fn spawn_level(
+    mut commands: Commands,
+    mut level_events: EventReader<AssetEvent<Level>>,
+    level_handle: Option<Res<LevelHandle>>,
+    levels: Res<Assets<Level>>,
+    level_entity_query: Query<Entity, With<LevelEntity>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let level_handle = match level_handle {
+        Some(level_handle) => level_handle,
+        None => return,
+    };
+
+    let mut changed = false;
+    for event in level_events.iter() {
+        match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                changed |= *handle == level_handle.0;
+            }
+            AssetEvent::Removed { .. } => {}
+        }
+    }
+
+    let level = match levels.get(&level_handle.0) {
+        Some(level) if changed => level,
+        _ => return,
+    };
+
+    for entity in level_entity_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    for (index, shape) in level.shapes.iter().enumerate() {
+        let [r, g, b] = shape.color;
+        // See through shapes are see through
+        let alpha = if shape.optics.is_some() { 0.4 } else { 1.0 };
+        let mut entity = commands.spawn_bundle(MaterialMesh2dBundle {
+            mesh: meshes.add(shape.mesh()).into(),
+            material: materials.add(ColorMaterial::from(Color::rgba(r, g, b, alpha))),
+            transform: Transform {
+                translation: to_vec2(shape.position).extend(1.0),
+                rotation: Quat::from_rotation_z(shape.rotation.to_radians()),
+                ..default()
+            },
+            ..default()
+        });
+        entity.insert(LevelEntity { index });
+
+        if let Some(collider) = shape.collider() {
+            entity
+                .insert(collider)
+                .insert(Friction::coefficient(shape.physics.friction))
+                .insert(Restitution::coefficient(shape.physics.restitution));
+            // Targets don't get in the way of anything, the puzzle checks them itself
+            if shape.physics.sensor || shape.role == Role::Target {
+                entity.insert(Sensor);
+            }
+            if let Some(optics) = shape.optics {
+                entity.insert(RefractiveIndex {
+                    index: optics.refractive_index,
+                    dispersion: optics.dispersion,
+                });
+            }
+        } else {
+            error!(
+                "Could not make a collider for level shape \"{}\"",
+                shape.name
+            );
+        }
+    }
+}
+
+// The asset server only logs why a level failed to load, so point at the log from the HUD too
+fn show_level_state(
+    asset_server: Res<AssetServer>,
+    level_handle: Option<Res<LevelHandle>>,
+    levels: Res<Assets<Level>>,
+    mut hud: ResMut<Hud>,
+) {
+    let level_handle = match level_handle {
+        Some(level_handle) => level_handle,
+        None => return,
+    };
+
+    let state = match asset_server.get_load_state(&level_handle.0) {
+        LoadState::Failed if levels.contains(&level_handle.0) => {
+            "reload failed, see the log. Keeping the last level"
+        }
+        LoadState::Failed => "failed to load, see the log",
+        LoadState::Loaded => "loaded",
+        _ => "loading",
+    };
+    hud.set("level", state);
+}
+
+// The arena that ships with the game, for the tests
+#[cfg(test)]
+pub fn arena_level() -> Level {
+    parse_level(
+        include_bytes!("../assets/levels/arena.level.ron"),
+        Path::new("levels/arena.level.ron"),
+    )
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arena_file() {
+        let level = arena_level();
+
+        let floor = level
+            .shapes
+            .iter()
+            .find(|shape| shape.name == "floor")
+            .unwrap();
+        assert_eq!(
+            floor.shape,
+            ShapeKind::Box {
+                half_extents: [600.0, 20.0]
+            }
+        );
+        assert_eq!(floor.position, [0.0, -500.0]);
+        let edges = floor.edges();
+        assert_eq!(edges.len(), 4);
+        assert!(edges
+            .iter()
+            .any(|(start, _)| start.abs_diff_eq(Vec2::new(600.0, -480.0), 1e-3)));
+        assert!(level.shapes.iter().all(|shape| shape.collider().is_some()));
+
+        let prism = level
+            .shapes
+            .iter()
+            .find(|shape| shape.name == "prism")
+            .unwrap();
+        assert!(prism.optics.unwrap().dispersion > 0.0);
+    }
+
+    #[test]
+    fn json_and_defaults() {
+        let json = r#"{
+            "shapes": [
+                { "shape": { "Circle": { "radius": 40 } }, "physics": { "restitution": 1 } }
+            ]
+        }"#;
+        let level = parse_level(json.as_bytes(), Path::new("bumpers.level.json")).unwrap();
+        let shape = &level.shapes[0];
+        assert_eq!(shape.shape, ShapeKind::Circle { radius: 40.0 });
+        assert_eq!(shape.position, [0.0, 0.0]);
+        assert_eq!(shape.color, default_color());
+        assert_eq!(shape.physics.restitution, 1.0);
+        assert_eq!(shape.physics.friction, 0.5);
+        assert_eq!(shape.role, Role::Wall);
+        assert_eq!(level.emitter, None);
+    }
+
+    #[test]
+    fn clear_errors() {
+        let path = Path::new("broken.level.ron");
+
+        // Cut off half way
+        let error = parse_level(b"(shapes: [(shape: Box(half_ext", path).unwrap_err();
+        assert!(matches!(error, LevelError::Ron(_)));
+
+        let error = parse_level(
+            b"(shapes: [(name: \"flat\", shape: Polygon(points: [(0.0, 0.0), (1.0, 0.0), (2.0, 0.0)]))])",
+            path,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "shape 0 \"flat\": a polygon has to be convex with its points in order"
+        );
+
+        let error = parse_level(
+            b"(shapes: [(name: \"goal\", shape: Circle(radius: 10.0), role: Target)])",
+            path,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "shape 0 \"goal\": a target needs an emitter in the level to aim at it"
+        );
+
+        let error = parse_level(b"{}", Path::new("level.toml")).unwrap_err();
+        assert!(matches!(error, LevelError::UnknownFormat(_)));
+    }
+}
@@ -1,5 +1,24 @@
-use bevy::prelude::*;
+use bevy::{
+    asset::AssetServerSettings,
+    prelude::*,
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+};
 use bevy_rapier2d::prelude::*;
+use mathcore::debug_draw::{DebugDraw, DebugDrawPlugin};
+use mathcore::hud::{Hud, HudPlugin};
+use mathcore::laser::{trace_laser, HitKind, LaserPath, RayHit, RefractiveIndex};
+use mathcore::mesh::MeshBuffers;
+use mathcore::player::{movement_input, rotation_input, Player, PlayerSettings};
+
+mod fog;
+mod level;
+mod projectile;
+mod puzzle;
+
+use fog::FogPlugin;
+use level::LevelPlugin;
+use projectile::ProjectilePlugin;
+use puzzle::{PuzzlePlugin, PuzzleState};
 
 const WINDOWHEIGHT: f32 = 1000.0;
 const WINDOWWIDTH: f32 = 1200.0;
@@ -9,6 +28,12 @@ const WINDOWWIDTH: f32 = 1200.0;
 // Good job!
 
 fn main() {
+    // A level in the assets folder can be given on the command line,
+    // `cargo run -- levels/puzzles/corner.level.ron` plays a puzzle
+    let level_path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "levels/arena.level.ron".to_string());
+
     App::new()
         .insert_resource(WindowDescriptor {
             title: "assignment 4".to_string(),
@@ -16,23 +41,51 @@ fn main() {
             height: WINDOWHEIGHT,
             ..Default::default()
         })
+        // Reload the level when its file is saved
+        .insert_resource(AssetServerSettings {
+            watch_for_changes: true,
+            ..default()
+        })
         .add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
         .add_plugins(DefaultPlugins)
+        .add_plugin(DebugDrawPlugin)
+        .add_plugin(HudPlugin::default())
         .add_startup_system(setup)
+        .add_plugin(LevelPlugin { path: level_path })
+        .add_plugin(PuzzlePlugin)
+        .add_plugin(FogPlugin {
+            half_size: Vec2::new(WINDOWWIDTH / 2.0, WINDOWHEIGHT / 2.0),
+        })
+        .add_startup_system(add_arena_bounds)
         .add_startup_system(add_player)
-        .add_system(move_player)
-        .add_system(rotate_player)
+        .insert_resource(PlayerSettings {
+            speed: 300.0,
+            // 360 degrees per second
+            rotation_speed: f32::to_radians(360.0),
+        })
+        .add_system(drive_player)
+        .add_plugin(ProjectilePlugin)
+        .insert_resource(RaySettings {
+            length: 2000.0,
+            max_bounces: 5,
+        })
+        .init_resource::<LaserPath>()
+        .add_startup_system(add_laser_mesh)
+        .add_system(change_ray_settings)
         .add_system(cast_ray)
+        .add_system(draw_laser.after(cast_ray))
+        .insert_resource(MarkerSettings {
+            lifetime: 3.0,
+            trail: false,
+            trail_length: 100,
+        })
+        .add_system(toggle_marker_trail)
+        // The markers cast_ray spawns only exist once the Update stage is over,
+        // so they are counted after that
+        .add_system_to_stage(CoreStage::PostUpdate, expire_markers)
         .run();
 }
 
-// The float value is the player movement speed in 'pixels/second'.
-#[derive(Component)]
-struct Player {
-    speed: f32,
-    rotation_speed: f32,
-}
-
 fn setup(mut commands: Commands) {
     commands.spawn_bundle(Camera2dBundle::default());
 
@@ -43,208 +96,510 @@ fn setup(mut commands: Commands) {
         },
         ..Default::default()
     });
+}
 
-    // The ceiling
-    let ceiling_size_x = WINDOWWIDTH;
-    let ceiling_size_y = 40.0;
-
+fn add_player(mut commands: Commands) {
     commands
-        .spawn()
-        .insert_bundle(SpriteBundle {
+        .spawn_bundle(SpriteBundle {
             sprite: Sprite {
-                color: Color::rgb(10.0, 70.0, 70.0),
-                custom_size: Some(Vec2::new(ceiling_size_x, ceiling_size_y)),
+                custom_size: Some(Vec2::new(30.0, 30.0)),
+                color: Color::ORANGE,
                 ..Default::default()
             },
-            transform: Transform::from_xyz(0.0, WINDOWHEIGHT / 2.0, 1.0),
+            transform: Transform::from_translation(Vec3::new(120.0, 0.0, 0.0)),
             ..Default::default()
         })
-        .insert(Collider::cuboid(ceiling_size_x / 2.0, ceiling_size_y / 2.0));
+        .insert(Player)
+        .insert(Collider::cuboid(30.0 / 2.0, 30.0 / 2.0))
+        // Physics moves the player so it can't go through walls. Without friction it slides
+        // along them and is fast enough to need continuous collision detection
+        .insert(RigidBody::Dynamic)
+        .insert(Velocity::zero())
+        .insert(GravityScale(0.0))
+        .insert(Friction::coefficient(0.0))
+        .insert(Ccd::enabled());
+}
 
-    // The floor
-    let floor_size_x = WINDOWWIDTH;
-    let floor_size_y = 40.0;
+// Keeps the player inside the window even when the level has no walls,
+// the level draws the walls so these are invisible
+fn add_arena_bounds(mut commands: Commands) {
+    let thickness = 50.0;
+    let half_width = WINDOWWIDTH / 2.0;
+    let half_height = WINDOWHEIGHT / 2.0;
+
+    for (position, half_extents) in [
+        (
+            Vec2::new(0.0, half_height + thickness),
+            Vec2::new(half_width, thickness),
+        ),
+        (
+            Vec2::new(0.0, -half_height - thickness),
+            Vec2::new(half_width, thickness),
+        ),
+        (
+            Vec2::new(half_width + thickness, 0.0),
+            Vec2::new(thickness, half_height),
+        ),
+        (
+            Vec2::new(-half_width - thickness, 0.0),
+            Vec2::new(thickness, half_height),
+        ),
+    ] {
+        commands
+            .spawn_bundle(TransformBundle::from_transform(
+                Transform::from_translation(position.extend(0.0)),
+            ))
+            .insert(Collider::cuboid(half_extents.x, half_extents.y));
+    }
+}
 
-    commands.spawn().insert_bundle(SpriteBundle {
-        sprite: Sprite {
-            color: Color::rgb(10.0, 70.0, 70.0),
-            custom_size: Some(Vec2::new(floor_size_x, floor_size_y)),
-            ..Default::default()
-        },
-        transform: Transform::from_xyz(0.0, -WINDOWHEIGHT / 2.0, 1.0),
-        ..Default::default()
-    });
+// Moves and turns the player through its velocity instead of its transform,
+// the same keys as the player controller. J and K turn the puzzle mirror instead when one is selected.
+fn drive_player(
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<PlayerSettings>,
+    puzzle_state: Option<Res<PuzzleState>>,
+    mut player_query: Query<&mut Velocity, With<Player>>,
+) {
+    let move_direction = movement_input(&keyboard_input);
+    let turning_mirror =
+        matches!(puzzle_state, Some(puzzle_state) if puzzle_state.selected.is_some());
+    let rotation_factor = if turning_mirror {
+        0.0
+    } else {
+        rotation_input(&keyboard_input)
+    };
+
+    for mut velocity in player_query.iter_mut() {
+        velocity.linvel = move_direction * settings.speed;
+        // Also stops bumping into walls from spinning the player
+        velocity.angvel = rotation_factor * settings.rotation_speed;
+    }
+}
+
+// How far the laser reaches from the front of the player in total, in pixels,
+// and how many times it can bounce before it stops
+struct RaySettings {
+    length: f32,
+    max_bounces: usize,
+}
+
+// Longer with R and shorter with F, more bounces with = and fewer with -
+fn change_ray_settings(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut ray_settings: ResMut<RaySettings>,
+    time: Res<Time>,
+) {
+    let mut lengthen = 0.0;
+    if keyboard_input.pressed(KeyCode::R) {
+        lengthen += 1.0;
+    }
+    if keyboard_input.pressed(KeyCode::F) {
+        lengthen -= 1.0;
+    }
 
-    // The Left Wall
-    let left_wall_size_x = 40.0;
-    let left_wall_size_y = WINDOWHEIGHT;
+    if lengthen != 0.0 {
+        ray_settings.length =
+            (ray_settings.length + lengthen * 300.0 * time.delta_seconds()).max(0.0);
+    }
 
+    if keyboard_input.just_pressed(KeyCode::Equals) {
+        ray_settings.max_bounces += 1;
+    }
+    if keyboard_input.just_pressed(KeyCode::Minus) {
+        ray_settings.max_bounces = ray_settings.max_bounces.saturating_sub(1);
+    }
+}
+
+// The line mesh showing the whole laser path, it is rebuilt every frame the laser is on.
+#[derive(Component)]
+struct LaserMesh;
+
+fn add_laser_mesh(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
     commands
-        .spawn()
-        .insert_bundle(SpriteBundle {
-            sprite: Sprite {
-                color: Color::rgb(10.0, 70.0, 70.0),
-                custom_size: Some(Vec2::new(left_wall_size_x, left_wall_size_y)),
-                ..Default::default()
-            },
-            transform: Transform::from_xyz(-WINDOWWIDTH / 2.0, 0.0, 1.0),
-            ..Default::default()
+        .spawn_bundle(MaterialMesh2dBundle {
+            mesh: meshes.add(MeshBuffers::new().into_mesh()).into(),
+            material: materials.add(ColorMaterial::from(Color::RED)),
+            transform: Transform::from_xyz(0.0, 0.0, 2.0),
+            ..default()
         })
-        .insert(Collider::cuboid(
-            left_wall_size_x / 2.0,
-            left_wall_size_y / 2.0,
-        ));
+        .insert(LaserMesh);
+}
+
+// The laser leaves the front of the player, the player faces along its local y axis
+// so it turns with the player when rotating with J and K.
+// Every time it hits a collider it reflects off it and carries on with the length it has left.
+// The path with every hit point and normal is kept in the LaserPath resource.
+#[allow(clippy::too_many_arguments)]
+fn cast_ray(
+    rapier_context: Res<RapierContext>,
+    ray_settings: Res<RaySettings>,
+    player_query: Query<(Entity, &Transform), With<Player>>,
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    refractive_query: Query<&RefractiveIndex>,
+    mut laser_path: ResMut<LaserPath>,
+    marker_settings: Res<MarkerSettings>,
+    mut next_marker: Local<u64>,
+    mut debug_draw: ResMut<DebugDraw>,
+    mut hud: ResMut<Hud>,
+) {
+    if !keyboard_input.pressed(KeyCode::Space) {
+        if !laser_path.points.is_empty() {
+            *laser_path = LaserPath::default();
+        }
+        return;
+    }
 
-    // The Right Wall
-    let right_wall_size_x = 40.0;
-    let right_wall_size_y = WINDOWHEIGHT;
+    let (player, transform) = player_query
+        .get_single()
+        .expect("Could not find a single player");
 
-    commands
-        .spawn()
-        .insert_bundle(SpriteBundle {
-            sprite: Sprite {
-                color: Color::rgb(10.0, 70.0, 70.0),
-                custom_size: Some(Vec2::new(right_wall_size_x, right_wall_size_y)),
-                ..Default::default()
+    let forward = transform.local_y().truncate();
+    // Just past the front edge of the player
+    let ray_pos = transform.translation.truncate() + forward * 17.0;
+    let solid = false;
+    // Don't hit the player when it is turned so the ray starts inside it
+    // and go through sensors like puzzle targets
+    let filter = QueryFilter::default()
+        .exclude_collider(player)
+        .exclude_sensors();
+
+    // `dispersion_scale` is -1 for red light, 0 for the laser itself and 1 for blue light
+    let trace = |dispersion_scale: f32| {
+        trace_laser(
+            ray_pos,
+            forward,
+            ray_settings.max_bounces,
+            ray_settings.length,
+            |origin, direction, max_toi| {
+                // This is similar to `QueryPipeline::cast_ray` except
+                // that it also returns the normal of the collider shape at the hit point.
+                // Not solid, so a ray starting inside a transparent shape hits its far side.
+                rapier_context
+                    .cast_ray_and_get_normal(origin, direction, max_toi, solid, filter)
+                    .map(|(entity, intersection)| RayHit {
+                        entity,
+                        toi: intersection.toi,
+                        normal: intersection.normal,
+                        refractive_index: refractive_query.get(entity).ok().map(
+                            |refractive_index| {
+                                refractive_index.index
+                                    + refractive_index.dispersion * dispersion_scale
+                            },
+                        ),
+                    })
             },
-            transform: Transform::from_xyz(WINDOWWIDTH / 2.0, 0.0, 1.0),
-            ..Default::default()
-        })
-        .insert(Collider::cuboid(
-            right_wall_size_x / 2.0,
-            right_wall_size_y / 2.0,
-        ));
+        )
+    };
+    *laser_path = trace(0.0);
+
+    // Something that splits light was hit, so show where the red and blue ends of the beam go
+    let splits = laser_path
+        .hits
+        .iter()
+        .any(|hit| match refractive_query.get(hit.entity) {
+            Ok(refractive_index) => refractive_index.dispersion != 0.0,
+            Err(_) => false,
+        });
+    if splits {
+        for (dispersion_scale, color) in [(-1.0, Color::ORANGE), (1.0, Color::VIOLET)] {
+            let beam = trace(dispersion_scale);
+            for segment in beam.points.windows(2) {
+                debug_draw.line(segment[0], segment[1], color);
+            }
+        }
+    }
+
+    for hit in laser_path.hits.iter() {
+        debug_draw.ray(hit.point, hit.normal * 40.0, Color::GREEN);
+    }
+
+    if let Some(first_hit) = laser_path.hits.first() {
+        // Mark the first point of contact and where the laser ends
+        let end = *laser_path.points.last().unwrap();
+        for position in [first_hit.point, end] {
+            spawn_marker(&mut commands, position, &marker_settings, *next_marker);
+            *next_marker += 1;
+        }
+
+        hud.set("hit normal", first_hit.normal);
+    } else {
+        hud.remove("hit normal");
+    }
+
+    hud.set("ray vec", forward * ray_settings.length);
+    hud.set("bounces", laser_path.hits.len().to_string());
+    let refractions = laser_path
+        .hits
+        .iter()
+        .filter(|hit| hit.kind == HitKind::Refracted)
+        .count();
+    hud.set("refractions", refractions.to_string());
+    hud.set("laser length", laser_path.length());
 }
 
-fn add_player(mut commands: Commands) {
+// Every shot marks where it hit. Markers go away after `lifetime` seconds,
+// and when there are more than the most allowed the oldest ones go first.
+// In trail mode the last `trail_length` markers stay around, otherwise only the latest shot's do.
+struct MarkerSettings {
+    lifetime: f32,
+    trail: bool,
+    trail_length: usize,
+}
+
+// A shot marks the first hit and the end of the laser
+const MARKERS_PER_SHOT: usize = 2;
+
+impl MarkerSettings {
+    fn max_markers(&self) -> usize {
+        if self.trail {
+            self.trail_length.max(MARKERS_PER_SHOT)
+        } else {
+            MARKERS_PER_SHOT
+        }
+    }
+}
+
+#[derive(Component)]
+struct Marker {
+    time_left: f32,
+    // Counts up with every marker, so the oldest has the lowest
+    order: u64,
+}
+
+fn spawn_marker(commands: &mut Commands, position: Vec2, settings: &MarkerSettings, order: u64) {
     commands
         .spawn_bundle(SpriteBundle {
             sprite: Sprite {
-                custom_size: Some(Vec2::new(30.0, 30.0)),
-                color: Color::ORANGE,
+                custom_size: Some(Vec2::new(4.0, 4.0)),
                 ..Default::default()
             },
-            transform: Transform::from_translation(Vec3::new(120.0, 0.0, 0.0)),
+            transform: Transform::from_translation(position.extend(0.0)),
             ..Default::default()
         })
-        .insert(Player {
-            speed: 300.0,
-            // degrees per second
-            rotation_speed: f32::to_radians(360.0),
-        })
-        .insert(Collider::cuboid(30.0 / 2.0, 30.0 / 2.0));
+        .insert(Marker {
+            time_left: settings.lifetime,
+            order,
+        });
 }
 
-fn move_player(
+// T switches trail mode on and off
+fn toggle_marker_trail(
     keyboard_input: Res<Input<KeyCode>>,
-    mut player_query: Query<(&Player, &mut Transform)>,
-    time: Res<Time>,
+    mut marker_settings: ResMut<MarkerSettings>,
+    mut hud: ResMut<Hud>,
 ) {
-    let (player, mut transform) = player_query
-        .get_single_mut()
-        .expect("Could not find single player");
-
-    // Get input from the keyboard (WASD)
-    let up: bool = keyboard_input.pressed(KeyCode::W) || keyboard_input.pressed(KeyCode::Up);
-    let down: bool = keyboard_input.pressed(KeyCode::S) || keyboard_input.pressed(KeyCode::Down);
-    let left: bool = keyboard_input.pressed(KeyCode::A) || keyboard_input.pressed(KeyCode::Left);
-    let right: bool = keyboard_input.pressed(KeyCode::D) || keyboard_input.pressed(KeyCode::Right);
+    if keyboard_input.just_pressed(KeyCode::T) {
+        marker_settings.trail = !marker_settings.trail;
+    }
+    if marker_settings.is_changed() {
+        hud.set("marker trail", marker_settings.trail.to_string());
+    }
+}
 
-    // If left is pressed than it will be -1, right 1, both they cancel out.
-    let x_axis: i8 = -(left as i8) + right as i8;
-    let y_axis: i8 = -(down as i8) + up as i8;
-    let move_delta: Vec2 = Vec2::new(x_axis as f32, y_axis as f32);
+fn expire_markers(
+    mut commands: Commands,
+    marker_settings: Res<MarkerSettings>,
+    mut marker_query: Query<(Entity, &mut Marker)>,
+    time: Res<Time>,
+) {
+    let mut alive = Vec::new();
+    for (entity, mut marker) in marker_query.iter_mut() {
+        marker.time_left -= time.delta_seconds();
+        if marker.time_left <= 0.0 {
+            commands.entity(entity).despawn();
+        } else {
+            alive.push((marker.order, entity));
+        }
+    }
 
-    // move the player
-    let delta_time = time.delta_seconds();
-    transform.translation.x += move_delta.x * player.speed * delta_time;
-    transform.translation.y += move_delta.y * player.speed * delta_time;
+    // Newest first, everything past the most allowed goes
+    alive.sort_unstable_by(|a, b| b.0.cmp(&a.0));
+    for (_, entity) in alive.iter().skip(marker_settings.max_markers()) {
+        commands.entity(*entity).despawn();
+    }
 }
 
-fn rotate_player(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut player_query: Query<(&Player, &mut Transform)>,
-    time: Res<Time>,
+fn draw_laser(
+    laser_path: Res<LaserPath>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut laser_query: Query<(&Mesh2dHandle, &mut Visibility), With<LaserMesh>>,
 ) {
-    let (player, mut transform) = player_query
-        .get_single_mut()
-        .expect("Could not find a single player");
+    if !laser_path.is_changed() {
+        return;
+    }
 
-    let mut rotation_factor = 0.0;
+    for (mesh_handle, mut visibility) in laser_query.iter_mut() {
+        visibility.is_visible = laser_path.points.len() >= 2;
+        if let Some(mesh) = meshes.get_mut(&mesh_handle.0) {
+            let mut buffers = MeshBuffers::new();
+            buffers.push_polyline(&laser_path.points, 3.0);
+            *mesh = buffers.into_mesh();
+        }
+    }
+}
 
-    if keyboard_input.pressed(KeyCode::J) {
-        rotation_factor += 1.0;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::hierarchy::HierarchyPlugin;
+    use bevy::transform::TransformPlugin;
+
+    fn laser_app(marker_settings: MarkerSettings) -> App {
+        let mut app = App::new();
+        let mut keyboard_input = Input::<KeyCode>::default();
+        keyboard_input.press(KeyCode::Space);
+
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(TransformPlugin)
+            .add_plugin(HierarchyPlugin)
+            .add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
+            .insert_resource(keyboard_input)
+            .init_resource::<DebugDraw>()
+            .init_resource::<Hud>()
+            .insert_resource(RaySettings {
+                length: 2000.0,
+                max_bounces: 5,
+            })
+            .init_resource::<LaserPath>()
+            .insert_resource(marker_settings)
+            .add_startup_system(add_ceiling)
+            .add_startup_system(add_player)
+            .add_system(cast_ray)
+            .add_system_to_stage(CoreStage::PostUpdate, expire_markers);
+        app
     }
 
-    if keyboard_input.pressed(KeyCode::K) {
-        rotation_factor -= 1.0;
+    // The level comes from a file through the asset server, which isn't here,
+    // so give the laser something to hit above the player
+    fn add_ceiling(mut commands: Commands) {
+        commands
+            .spawn_bundle(TransformBundle::from_transform(Transform::from_xyz(
+                0.0, 500.0, 1.0,
+            )))
+            .insert(Collider::cuboid(600.0, 20.0));
     }
 
-    // update the player rotation around the Z axis (perpendicular to the 2D plane of the screen)
-    let delta_time = time.delta_seconds();
-    transform.rotate(Quat::from_rotation_z(
-        rotation_factor * player.rotation_speed * delta_time,
-    ));
-}
+    fn count_markers(app: &mut App) -> usize {
+        app.world
+            .query_filtered::<Entity, With<Marker>>()
+            .iter(&app.world)
+            .count()
+    }
 
-// TODO
-// When you better understand how to make your ray rotate with the player
-// you can come back to this
-fn cast_ray(
-    rapier_context: Res<RapierContext>,
-    player_query: Query<&Transform, With<Player>>,
-    mut commands: Commands,
-    keyboard_input: Res<Input<KeyCode>>,
-) {
-    if keyboard_input.pressed(KeyCode::Space) {
-        let transform = player_query
-            .get_single()
-            .expect("Could not find a single player");
-
-        let ray_pos = Vec2::new(transform.translation.x, transform.translation.y + 17.0);
-        // Ray moves diagonally
-        let ray_vec = Vec2::new(80.0, 80.0);
-        let max_toi = 1.0;
-        let solid = false;
-        let filter = QueryFilter::default();
-        if let Some((_entity, intersection)) =
-            rapier_context.cast_ray_and_get_normal(ray_pos, ray_vec, max_toi, solid, filter)
-        {
-            // This is similar to `QueryPipeline::cast_ray` illustrated above except
-            // that it also returns the normal of the collider shape at the hit point.
-            let hit_point: Vect = intersection.point;
-            let hit_normal: Vect = intersection.normal;
-
-            // spawn a rect at the point of contact
-            commands.spawn_bundle(SpriteBundle {
-                sprite: Sprite {
-                    custom_size: Some(Vec2::new(4.0, 4.0)),
-                    ..Default::default()
-                },
-                transform: Transform::from_translation(hit_point.extend(0.0)),
-                ..Default::default()
-            });
+    // Space held for 10,000 frames, the number of entities has to stop growing
+    fn hold_space(marker_settings: MarkerSettings) {
+        let max_markers = marker_settings.max_markers();
+        let mut app = laser_app(marker_settings);
+
+        // Let rapier add the walls to its query pipeline first
+        app.update();
+        app.update();
+        let entities_before = app.world.entities().len();
+
+        let mut saw_markers = false;
+        for _ in 0..10_000 {
+            app.update();
+            let markers = count_markers(&mut app);
+            assert!(markers <= max_markers, "{} markers", markers);
+            saw_markers |= markers > 0;
+        }
 
-            let projected_ray_onto_normal: Vec2 = hit_normal.dot(ray_vec) * hit_normal;
+        assert!(saw_markers, "the laser never hit anything");
+        assert!(app.world.entities().len() <= entities_before + max_markers as u32);
+    }
 
-            // Remember a vector does not have a position, it has a direction and a magnitude.
-            let reflected_vec = ray_vec - (2.0 * projected_ray_onto_normal);
+    #[test]
+    fn markers_stay_bounded() {
+        hold_space(MarkerSettings {
+            lifetime: 3.0,
+            trail: false,
+            trail_length: 100,
+        });
+    }
 
-            // add the contact point to get the position
-            let reflected_pos = hit_point + reflected_vec;
+    #[test]
+    fn trail_stays_bounded() {
+        hold_space(MarkerSettings {
+            lifetime: 1000.0,
+            trail: true,
+            trail_length: 50,
+        });
+    }
 
-            println!("ray vec is {}", ray_vec);
-            println!("Reflected pos is {}", reflected_pos);
-            println!("Reflected vec is {}", reflected_vec);
+    // Holds the keys down with only the arena bounds around and returns where the player ends up
+    fn drive(speed: f32, keys: &[KeyCode], frames: usize) -> Vec2 {
+        let mut app = App::new();
+        let mut keyboard_input = Input::<KeyCode>::default();
+        for key in keys {
+            keyboard_input.press(*key);
+        }
 
-            commands.spawn_bundle(SpriteBundle {
-                sprite: Sprite {
-                    custom_size: Some(Vec2::new(4.0, 4.0)),
-                    ..Default::default()
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(TransformPlugin)
+            .add_plugin(HierarchyPlugin)
+            .add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
+            // The same step every frame no matter how fast the test runs
+            .insert_resource(RapierConfiguration {
+                timestep_mode: TimestepMode::Fixed {
+                    dt: 1.0 / 60.0,
+                    substeps: 1,
                 },
-                transform: Transform::from_translation(reflected_pos.extend(0.0)),
-                ..Default::default()
-            });
+                ..default()
+            })
+            .insert_resource(keyboard_input)
+            .insert_resource(PlayerSettings {
+                speed,
+                rotation_speed: 0.0,
+            })
+            .add_startup_system(add_arena_bounds)
+            .add_startup_system(add_player)
+            .add_system(drive_player);
+
+        for _ in 0..frames {
+            app.update();
         }
+
+        let mut player_query = app.world.query_filtered::<&GlobalTransform, With<Player>>();
+        player_query.single(&app.world).translation().truncate()
+    }
+
+    fn assert_inside_arena(position: Vec2) {
+        assert!(
+            position.x.abs() < WINDOWWIDTH / 2.0 && position.y.abs() < WINDOWHEIGHT / 2.0,
+            "the player got out to {}",
+            position
+        );
+    }
+
+    #[test]
+    fn player_slides_along_walls() {
+        // Up and to the right, the right wall comes first and then the player slides up it
+        let position = drive(300.0, &[KeyCode::D, KeyCode::W], 300);
+        assert_inside_arena(position);
+        assert!(
+            position.x > WINDOWWIDTH / 2.0 - 30.0,
+            "stopped at {}",
+            position
+        );
+        assert!(
+            position.y > WINDOWHEIGHT / 2.0 - 30.0,
+            "stuck at {}",
+            position
+        );
+    }
+
+    #[test]
+    fn fast_player_does_not_tunnel() {
+        // Further than the walls are thick every step
+        let position = drive(30_000.0, &[KeyCode::A], 100);
+        assert_inside_arena(position);
+        let position = drive(30_000.0, &[KeyCode::S], 100);
+        assert_inside_arena(position);
     }
 }
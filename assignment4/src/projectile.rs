@@ -0,0 +1,319 @@
+use bevy::prelude::*;
+use bevy::sprite::MaterialMesh2dBundle;
+use bevy_rapier2d::prelude::*;
+use mathcore::debug_draw::DebugDraw;
+use mathcore::laser::{reflect, trace_laser, LaserPath, RayHit, SURFACE_OFFSET};
+use mathcore::player::Player;
+
+// A ball that ricochets off walls. Hold E to see where it will go and let go to fire it.
+// Every frame it sweeps its collider along the way it is going, so it can't skip through a wall
+// however fast it goes, and bounces with the same reflection as the laser, v - 2(n·v)n.
+pub struct ProjectilePlugin;
+
+impl Plugin for ProjectilePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ProjectileSettings {
+            speed: 800.0,
+            radius: 6.0,
+            max_bounces: 8,
+            energy_loss: 0.1,
+        })
+        .add_event::<ProjectileDespawned>()
+        .add_system(aim_projectile)
+        .add_system(move_projectiles);
+    }
+}
+
+pub struct ProjectileSettings {
+    // Pixels per second when it is fired
+    pub speed: f32,
+    pub radius: f32,
+    // It stops at the wall after this many bounces
+    pub max_bounces: usize,
+    // The fraction of its speed it loses every bounce, from 0 to 1
+    pub energy_loss: f32,
+}
+
+// Slower than this it has run out of energy
+const MIN_SPEED: f32 = 1.0;
+
+// How far ahead the prediction looks, further than it ever goes in the arena
+const PREDICTION_LENGTH: f32 = 100_000.0;
+
+#[derive(Component)]
+pub struct Projectile {
+    pub direction: Vec2,
+    pub speed: f32,
+    pub bounces: usize,
+    // Where it was fired from and every wall it hit since
+    pub path: Vec<Vec2>,
+}
+
+impl Projectile {
+    pub fn new(origin: Vec2, direction: Vec2, speed: f32) -> Self {
+        Projectile {
+            direction: direction.normalize_or_zero(),
+            speed,
+            bounces: 0,
+            path: vec![origin],
+        }
+    }
+}
+
+// Sent when a projectile used up its bounces or its speed, right before it is despawned
+pub struct ProjectileDespawned {
+    pub entity: Entity,
+    pub position: Vec2,
+    pub bounces: usize,
+    pub path: Vec<Vec2>,
+}
+
+// The first thing a ball of `ball` moving from `origin` along `direction` runs into.
+// Sensors don't stop it.
+fn cast_ball(
+    rapier_context: &RapierContext,
+    ball: &Collider,
+    origin: Vec2,
+    direction: Vec2,
+    max_toi: f32,
+) -> Option<RayHit> {
+    let filter = QueryFilter::default().exclude_sensors();
+    rapier_context
+        .cast_shape(origin, 0.0, direction, ball, max_toi, filter)
+        .map(|(entity, toi)| RayHit {
+            entity,
+            toi: toi.toi,
+            // normal1 points out of the ball at the contact, so the wall's normal is the opposite.
+            // The ball isn't rotated so it is already in world space.
+            normal: -toi.normal1,
+            refractive_index: None,
+        })
+}
+
+// Where a projectile will go, the same tracing as the laser but with a ball instead of a ray,
+// and without going through see through shapes.
+pub fn predict_projectile(
+    rapier_context: &RapierContext,
+    settings: &ProjectileSettings,
+    origin: Vec2,
+    direction: Vec2,
+) -> LaserPath {
+    let ball = Collider::ball(settings.radius);
+    trace_laser(
+        origin,
+        direction,
+        settings.max_bounces,
+        PREDICTION_LENGTH,
+        |origin, direction, max_toi| cast_ball(rapier_context, &ball, origin, direction, max_toi),
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn aim_projectile(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<ProjectileSettings>,
+    rapier_context: Res<RapierContext>,
+    player_query: Query<&Transform, With<Player>>,
+    mut debug_draw: ResMut<DebugDraw>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let holding = keyboard_input.pressed(KeyCode::E);
+    let released = keyboard_input.just_released(KeyCode::E);
+    if !holding && !released {
+        return;
+    }
+
+    let transform = match player_query.get_single() {
+        Ok(transform) => transform,
+        Err(_) => return,
+    };
+    let forward = transform.local_y().truncate();
+    // Clear of the front edge of the player
+    let origin = transform.translation.truncate() + forward * (17.0 + settings.radius);
+
+    if holding {
+        let prediction = predict_projectile(&rapier_context, &settings, origin, forward);
+        for points in prediction.points.windows(2) {
+            debug_draw.line(points[0], points[1], Color::YELLOW);
+        }
+    }
+
+    if released {
+        commands
+            .spawn_bundle(MaterialMesh2dBundle {
+                mesh: meshes
+                    .add(Mesh::from(shape::Circle::new(settings.radius)))
+                    .into(),
+                material: materials.add(ColorMaterial::from(Color::YELLOW)),
+                transform: Transform::from_translation(origin.extend(2.0)),
+                ..default()
+            })
+            .insert(Projectile::new(origin, forward, settings.speed));
+    }
+}
+
+fn move_projectiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<ProjectileSettings>,
+    rapier_context: Res<RapierContext>,
+    mut projectile_query: Query<(Entity, &mut Transform, &mut Projectile)>,
+    mut despawned_events: EventWriter<ProjectileDespawned>,
+) {
+    let ball = Collider::ball(settings.radius);
+
+    for (entity, mut transform, mut projectile) in projectile_query.iter_mut() {
+        let mut position = transform.translation.truncate();
+        let mut distance_left = projectile.speed * time.delta_seconds();
+        let mut out_of_energy = false;
+
+        // It can hit more than one wall in a frame when it is fast or in a corner
+        while distance_left > 0.0 {
+            let direction = projectile.direction;
+            let hit = match cast_ball(&rapier_context, &ball, position, direction, distance_left) {
+                Some(hit) if hit.toi <= distance_left => hit,
+                _ => {
+                    position += direction * distance_left;
+                    break;
+                }
+            };
+
+            // The same steps as `trace_laser` takes for a reflection, so it stays on the prediction
+            let point = position + direction * hit.toi;
+            distance_left -= hit.toi;
+            projectile.path.push(point);
+
+            if projectile.bounces >= settings.max_bounces {
+                position = point;
+                out_of_energy = true;
+                break;
+            }
+
+            let facing_normal = if direction.dot(hit.normal) < 0.0 {
+                hit.normal
+            } else {
+                -hit.normal
+            };
+            projectile.direction = reflect(direction, facing_normal).normalize_or_zero();
+            position = point + facing_normal * SURFACE_OFFSET;
+            projectile.bounces += 1;
+
+            let energy_left = 1.0 - settings.energy_loss;
+            projectile.speed *= energy_left;
+            distance_left *= energy_left;
+            if projectile.speed < MIN_SPEED {
+                out_of_energy = true;
+                break;
+            }
+        }
+
+        transform.translation = position.extend(transform.translation.z);
+
+        if out_of_energy {
+            despawned_events.send(ProjectileDespawned {
+                entity,
+                position,
+                bounces: projectile.bounces,
+                path: projectile.path.clone(),
+            });
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::event::Events;
+    use bevy::hierarchy::HierarchyPlugin;
+    use bevy::transform::TransformPlugin;
+
+    fn add_walls(mut commands: Commands) {
+        for (position, rotation, collider) in [
+            (Vec2::new(0.0, 300.0), 0.0, Collider::cuboid(400.0, 20.0)),
+            (Vec2::new(0.0, -300.0), 0.0, Collider::cuboid(400.0, 20.0)),
+            (Vec2::new(-400.0, 0.0), 0.0, Collider::cuboid(20.0, 300.0)),
+            (Vec2::new(400.0, 0.0), 0.0, Collider::cuboid(20.0, 300.0)),
+            (Vec2::new(-150.0, 100.0), 0.0, Collider::ball(40.0)),
+            (Vec2::new(150.0, -80.0), 0.5, Collider::cuboid(60.0, 15.0)),
+        ] {
+            commands
+                .spawn_bundle(TransformBundle::from_transform(Transform {
+                    translation: position.extend(0.0),
+                    rotation: Quat::from_rotation_z(rotation),
+                    ..default()
+                }))
+                .insert(collider);
+        }
+    }
+
+    #[test]
+    fn follows_the_prediction() {
+        let settings = ProjectileSettings {
+            // Fast so the test doesn't wait on real time
+            speed: 1_000_000.0,
+            radius: 6.0,
+            max_bounces: 6,
+            energy_loss: 0.2,
+        };
+        let origin = Vec2::new(10.0, -20.0);
+        let direction = Vec2::new(1.0, 0.35);
+
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(TransformPlugin)
+            .add_plugin(HierarchyPlugin)
+            .add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
+            .insert_resource(settings)
+            .add_event::<ProjectileDespawned>()
+            .add_startup_system(add_walls)
+            .add_system(move_projectiles);
+
+        // Let rapier add the walls to its query pipeline first
+        app.update();
+        app.update();
+
+        let prediction = predict_projectile(
+            app.world.resource::<RapierContext>(),
+            app.world.resource::<ProjectileSettings>(),
+            origin,
+            direction,
+        );
+        assert_eq!(prediction.hits.len(), 7);
+
+        app.world
+            .spawn()
+            .insert_bundle(TransformBundle::from_transform(
+                Transform::from_translation(origin.extend(0.0)),
+            ))
+            .insert(Projectile::new(origin, direction, 1_000_000.0));
+
+        let mut despawned = None;
+        for _ in 0..100_000 {
+            app.update();
+            let mut events = app.world.resource_mut::<Events<ProjectileDespawned>>();
+            if let Some(event) = events.drain().next() {
+                despawned = Some(event);
+                break;
+            }
+        }
+        let despawned = despawned.expect("the projectile never ran out");
+
+        assert_eq!(despawned.bounces, 6);
+        assert_eq!(despawned.path.len(), prediction.points.len());
+        for (point, predicted) in despawned.path.iter().zip(prediction.points.iter()) {
+            assert!(
+                point.distance(*predicted) < 1e-2,
+                "went through {} instead of {}",
+                point,
+                predicted
+            );
+        }
+        assert_eq!(despawned.position, *despawned.path.last().unwrap());
+
+        let mut projectile_query = app.world.query::<&Projectile>();
+        assert_eq!(projectile_query.iter(&app.world).count(), 0);
+    }
+}
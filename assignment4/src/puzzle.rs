@@ -0,0 +1,357 @@
+use crate::level::{Level, LevelEntity, LevelHandle, LevelShape, Role};
+use bevy::prelude::*;
+use mathcore::cursor::cursor_world_position;
+use mathcore::debug_draw::DebugDraw;
+use mathcore::hud::Hud;
+use mathcore::laser::{trace_laser, LaserPath, RayHit};
+use mathcore::player::rotation_input;
+
+// Levels with an emitter are puzzles. The emitter's laser bounces off the level like the
+// player's does, and the puzzle is solved when it goes through every target.
+// Click a mirror to select it and turn it with J and K, Escape or clicking away lets go of it.
+// Everything here works on the level itself instead of rapier, so puzzles can be checked headless.
+pub struct PuzzlePlugin;
+
+impl Plugin for PuzzlePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PuzzleState>()
+            .add_system(reset_puzzle)
+            .add_system(select_mirror.after(reset_puzzle))
+            .add_system(turn_mirror.after(select_mirror))
+            .add_system(show_puzzle.after(turn_mirror));
+    }
+}
+
+#[derive(Default)]
+pub struct PuzzleState {
+    // Degrees counter clockwise, for every shape in the level
+    pub rotations: Vec<f32>,
+    // The index of the mirror J and K turn, the player doesn't turn while one is selected
+    pub selected: Option<usize>,
+    pub solved: bool,
+}
+
+// Degrees per second
+const MIRROR_TURN_SPEED: f32 = 45.0;
+
+// How close to a mirror a click has to be to select it
+const SELECT_DISTANCE: f32 = 60.0;
+
+pub fn shape_rotations(level: &Level) -> Vec<f32> {
+    level.shapes.iter().map(|shape| shape.rotation).collect()
+}
+
+fn blocks_beam(shape: &LevelShape) -> bool {
+    shape.role != Role::Target && !shape.physics.sensor
+}
+
+// The path of the emitter's laser with every shape turned to `rotations`
+pub fn trace_beam(level: &Level, rotations: &[f32]) -> LaserPath {
+    let emitter = match level.emitter {
+        Some(emitter) => emitter,
+        None => return LaserPath::default(),
+    };
+
+    trace_laser(
+        Vec2::from(emitter.position),
+        Vec2::from_angle(emitter.direction.to_radians()),
+        emitter.max_bounces,
+        emitter.length,
+        |origin, direction, max_toi| {
+            level
+                .shapes
+                .iter()
+                .enumerate()
+                .filter(|(_, shape)| blocks_beam(shape))
+                .filter_map(|(index, shape)| {
+                    let hit = shape.cast_ray(rotations[index], origin, direction, max_toi)?;
+                    Some(RayHit {
+                        // Not a real entity, the tracing only uses it to tell shapes apart
+                        entity: Entity::from_raw(index as u32),
+                        toi: hit.toi,
+                        normal: hit.normal,
+                        refractive_index: shape.optics.map(|optics| optics.refractive_index),
+                    })
+                })
+                .min_by(|a, b| a.toi.partial_cmp(&b.toi).unwrap())
+        },
+    )
+}
+
+// For every target its index in the level and whether the path goes through it
+pub fn targets_hit(level: &Level, rotations: &[f32], path: &LaserPath) -> Vec<(usize, bool)> {
+    level
+        .shapes
+        .iter()
+        .enumerate()
+        .filter(|(_, shape)| shape.role == Role::Target)
+        .map(|(index, shape)| {
+            let hit = path.points.windows(2).any(|points| {
+                let offset = points[1] - points[0];
+                let length = offset.length();
+                length > 0.0
+                    && shape
+                        .cast_ray(rotations[index], points[0], offset / length, length)
+                        .is_some()
+            });
+            (index, hit)
+        })
+        .collect()
+}
+
+pub fn is_solved(level: &Level, rotations: &[f32]) -> bool {
+    let targets = targets_hit(level, rotations, &trace_beam(level, rotations));
+    !targets.is_empty() && targets.iter().all(|(_, hit)| *hit)
+}
+
+// Look for mirror rotations that solve the puzzle, trying every `step_degrees`.
+// Only mirrors the beam actually hits matter, so it turns the first mirror along the beam
+// that hasn't been tried yet, and for every angle of it carries on with the next one the beam hits.
+pub fn solve(level: &Level, step_degrees: f32) -> Option<Vec<f32>> {
+    let mut rotations = shape_rotations(level);
+    let mut turned = vec![false; level.shapes.len()];
+    if search(level, step_degrees, &mut rotations, &mut turned) {
+        Some(rotations)
+    } else {
+        None
+    }
+}
+
+fn search(level: &Level, step_degrees: f32, rotations: &mut [f32], turned: &mut [bool]) -> bool {
+    let path = trace_beam(level, rotations);
+    let targets = targets_hit(level, rotations, &path);
+    if !targets.is_empty() && targets.iter().all(|(_, hit)| *hit) {
+        return true;
+    }
+
+    let mirror = path
+        .hits
+        .iter()
+        .map(|hit| hit.entity.id() as usize)
+        .find(|index| level.shapes[*index].role == Role::Mirror && !turned[*index]);
+    let mirror = match mirror {
+        Some(mirror) => mirror,
+        None => return false,
+    };
+
+    turned[mirror] = true;
+    let start = rotations[mirror];
+    let steps = (360.0 / step_degrees).round() as usize;
+    for step in 0..steps {
+        rotations[mirror] = start + step as f32 * step_degrees;
+        if search(level, step_degrees, rotations, turned) {
+            return true;
+        }
+    }
+    rotations[mirror] = start;
+    turned[mirror] = false;
+    false
+}
+
+fn reset_puzzle(
+    mut level_events: EventReader<AssetEvent<Level>>,
+    level_handle: Option<Res<LevelHandle>>,
+    levels: Res<Assets<Level>>,
+    mut puzzle_state: ResMut<PuzzleState>,
+) {
+    let level_handle = match level_handle {
+        Some(level_handle) => level_handle,
+        None => return,
+    };
+
+    for event in level_events.iter() {
+        if let AssetEvent::Created { handle } | AssetEvent::Modified { handle } = event {
+            if let Some(level) = levels.get(handle).filter(|_| *handle == level_handle.0) {
+                *puzzle_state = PuzzleState {
+                    rotations: shape_rotations(level),
+                    ..default()
+                };
+            }
+        }
+    }
+}
+
+fn select_mirror(
+    windows: Res<Windows>,
+    mouse_input: Res<Input<MouseButton>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    level_handle: Option<Res<LevelHandle>>,
+    levels: Res<Assets<Level>>,
+    mut puzzle_state: ResMut<PuzzleState>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        puzzle_state.selected = None;
+    }
+    if !mouse_input.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    let level = match level_handle.and_then(|level_handle| levels.get(&level_handle.0)) {
+        Some(level) => level,
+        None => return,
+    };
+    let (camera, camera_transform) = camera_query
+        .get_single()
+        .expect("Could not find a single camera");
+    let cursor_position = match cursor_world_position(&windows, camera, camera_transform) {
+        Some(cursor_position) => cursor_position,
+        None => return,
+    };
+
+    // The closest mirror to the click, or nothing when the click is away from all of them
+    puzzle_state.selected = level
+        .shapes
+        .iter()
+        .enumerate()
+        .filter(|(_, shape)| shape.role == Role::Mirror)
+        .map(|(index, shape)| (index, Vec2::from(shape.position).distance(cursor_position)))
+        .filter(|(_, distance)| *distance < SELECT_DISTANCE)
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .map(|(index, _)| index);
+}
+
+fn turn_mirror(
+    keyboard_input: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    level_handle: Option<Res<LevelHandle>>,
+    levels: Res<Assets<Level>>,
+    mut puzzle_state: ResMut<PuzzleState>,
+    mut level_entity_query: Query<(&LevelEntity, &mut Transform)>,
+) {
+    if let Some(selected) = puzzle_state.selected {
+        let turn = rotation_input(&keyboard_input) * MIRROR_TURN_SPEED * time.delta_seconds();
+        // Only borrow the state mutably when it really turns, so it isn't marked as changed
+        // and the transforms aren't written every frame a mirror is selected
+        if turn != 0.0 {
+            if let Some(rotation) = puzzle_state.rotations.get_mut(selected) {
+                *rotation += turn;
+            }
+        }
+    }
+
+    if !puzzle_state.is_changed() {
+        return;
+    }
+    let level = match level_handle.and_then(|level_handle| levels.get(&level_handle.0)) {
+        Some(level) => level,
+        None => return,
+    };
+
+    // Only mirrors turn, and the level sets everything else up the way it is in the file
+    for (level_entity, mut transform) in level_entity_query.iter_mut() {
+        let is_mirror = matches!(
+            level.shapes.get(level_entity.index),
+            Some(shape) if shape.role == Role::Mirror
+        );
+        if !is_mirror {
+            continue;
+        }
+        if let Some(rotation) = puzzle_state.rotations.get(level_entity.index) {
+            transform.rotation = Quat::from_rotation_z(rotation.to_radians());
+        }
+    }
+}
+
+fn show_puzzle(
+    level_handle: Option<Res<LevelHandle>>,
+    levels: Res<Assets<Level>>,
+    mut puzzle_state: ResMut<PuzzleState>,
+    mut debug_draw: ResMut<DebugDraw>,
+    mut hud: ResMut<Hud>,
+) {
+    let level = match level_handle.and_then(|level_handle| levels.get(&level_handle.0)) {
+        Some(level) if level.emitter.is_some() => level,
+        _ => return,
+    };
+    if puzzle_state.rotations.len() != level.shapes.len() {
+        return;
+    }
+
+    let path = trace_beam(level, &puzzle_state.rotations);
+    let targets = targets_hit(level, &puzzle_state.rotations, &path);
+    let hit_count = targets.iter().filter(|(_, hit)| *hit).count();
+    let solved = !targets.is_empty() && hit_count == targets.len();
+    if solved && !puzzle_state.solved {
+        info!("Puzzle solved!");
+    }
+    // Only write when it changes, so turning the mirrors is what marks the state as changed
+    if puzzle_state.solved != solved {
+        puzzle_state.solved = solved;
+    }
+
+    let beam_color = if solved { Color::GREEN } else { Color::CYAN };
+    for points in path.points.windows(2) {
+        debug_draw.line(points[0], points[1], beam_color);
+    }
+    if let Some(first) = path.points.first() {
+        debug_draw.circle(*first, 10.0, beam_color);
+    }
+
+    for (index, hit) in targets.iter() {
+        let color = if *hit { Color::GREEN } else { Color::RED };
+        debug_draw.circle(Vec2::from(level.shapes[*index].position), 12.0, color);
+    }
+    if let Some(selected) = puzzle_state.selected {
+        debug_draw.circle(
+            Vec2::from(level.shapes[selected].position),
+            SELECT_DISTANCE,
+            Color::YELLOW,
+        );
+    }
+
+    if solved {
+        hud.set("puzzle", "solved!");
+    } else {
+        hud.set(
+            "puzzle",
+            format!(
+                "{}/{} targets, click a mirror and turn it with J and K",
+                hit_count,
+                targets.len()
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::{arena_level, parse_level};
+
+    #[test]
+    fn arena_is_not_a_puzzle() {
+        let level = arena_level();
+        assert!(trace_beam(&level, &shape_rotations(&level))
+            .points
+            .is_empty());
+        assert!(!is_solved(&level, &shape_rotations(&level)));
+    }
+
+    #[test]
+    fn shipped_puzzles_are_solvable() {
+        let mut puzzles = 0;
+        for entry in std::fs::read_dir("assets/levels/puzzles").unwrap() {
+            let path = entry.unwrap().path();
+            let level = parse_level(&std::fs::read(&path).unwrap(), &path).unwrap();
+            let rotations = shape_rotations(&level);
+            assert!(
+                !is_solved(&level, &rotations),
+                "{} starts out solved",
+                path.display()
+            );
+
+            let solution =
+                solve(&level, 1.0).unwrap_or_else(|| panic!("{} can't be solved", path.display()));
+            assert!(is_solved(&level, &solution));
+            // Only by turning mirrors
+            for (index, shape) in level.shapes.iter().enumerate() {
+                if shape.role != Role::Mirror {
+                    assert_eq!(solution[index], rotations[index]);
+                }
+            }
+            puzzles += 1;
+        }
+        assert!(puzzles > 0, "no puzzles in assets/levels/puzzles");
+    }
+}
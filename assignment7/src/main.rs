@@ -1,18 +1,48 @@
-use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use bevy::{
+    prelude::*,
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+};
+use mathcore::debug_draw::{DebugDraw, DebugDrawPlugin};
+use mathcore::hud::{Hud, HudPlugin};
+use mathcore::mesh::{LineJoin, MeshBuffers};
 
-const TAU: f32 = 6.283185;
+mod regular_polygon;
+
+use regular_polygon::{
+    filled_regular_polygon, regular_polygon_outline, regular_polygon_points, star_polygon_loops,
+    star_polygon_outline,
+};
+
+const NUM_POINTS: u32 = 5;
+const RADIUS: f32 = 50.0;
+// Radians, turns the whole polygon so a point faces up
+const ROTATION: f32 = std::f32::consts::FRAC_PI_2;
+const OUTLINE_WIDTH: f32 = 6.0;
+
+const STAR_RADIUS: f32 = 100.0;
+const STAR_CENTER: Vec2 = Vec2::new(0.0, -200.0);
+// The most corners a star can have
+const MAX_STAR_POINTS: u32 = 24;
 
 fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
+        .add_plugin(DebugDrawPlugin)
+        .add_plugin(HudPlugin::default())
         .add_startup_system(draw_regular_polygons)
+        .add_system(draw_vertex_vectors)
+        .insert_resource(StarSettings { n: 5, k: 2 })
+        .add_startup_system(add_star_mesh)
+        .add_system(change_star)
+        .add_system(draw_star.after(change_star))
         .run()
 }
 
 // Draw a regular polygon
 // A regular polygon has the same side length and the same angle between all the sides
 // Draw the polygon by calculating the angle between all the points with TAU / the number of points.
-// Then calculate where to put the points by using from_angle and multiplying the angle by 1 -> num of points.
+// Then calculate where to put the points by using from_angle and multiplying the angle by 0 -> num of points - 1.
+// The middle one is filled in, with a mitered outline on the left and a beveled one on the right.
 fn draw_regular_polygons(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
@@ -20,18 +50,120 @@ fn draw_regular_polygons(
 ) {
     commands.spawn_bundle(Camera2dBundle::default());
 
-    let num_points = 5;
-    let angle_between_points = TAU / num_points as f32;
+    let shapes = [
+        (
+            -3.0 * RADIUS,
+            regular_polygon_outline(
+                NUM_POINTS,
+                RADIUS,
+                ROTATION,
+                OUTLINE_WIDTH,
+                LineJoin::Miter { limit: 4.0 },
+            ),
+        ),
+        (0.0, filled_regular_polygon(NUM_POINTS, RADIUS, ROTATION)),
+        (
+            3.0 * RADIUS,
+            regular_polygon_outline(NUM_POINTS, RADIUS, ROTATION, OUTLINE_WIDTH, LineJoin::Bevel),
+        ),
+    ];
 
-    for point in 1..=num_points {
-        let vec = Vec2::from_angle(point as f32 * angle_between_points) * 50.0;
-
-        // Circle
+    for (x, buffers) in shapes {
         commands.spawn_bundle(MaterialMesh2dBundle {
-            mesh: meshes.add(shape::Circle::new(5.0).into()).into(),
+            mesh: meshes.add(buffers.into_mesh()).into(),
             material: materials.add(ColorMaterial::from(Color::PURPLE)),
-            transform: Transform::from_translation(vec.extend(0.0)),
+            transform: Transform::from_xyz(x, 0.0, 0.0),
             ..default()
         });
     }
 }
+
+// Show the vector from the middle of the polygon to every point
+fn draw_vertex_vectors(mut debug_draw: ResMut<DebugDraw>) {
+    for vec in regular_polygon_points(NUM_POINTS, RADIUS, ROTATION) {
+        debug_draw.arrow(Vec2::ZERO, vec, Color::WHITE);
+    }
+}
+
+// The star polygon {n/k}, every k-th of n corners connected.
+// Up and Down change n and Right and Left change k.
+struct StarSettings {
+    n: u32,
+    k: u32,
+}
+
+#[derive(Component)]
+struct StarMesh;
+
+fn add_star_mesh(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands
+        .spawn_bundle(MaterialMesh2dBundle {
+            mesh: meshes.add(MeshBuffers::new().into_mesh()).into(),
+            material: materials.add(ColorMaterial::from(Color::GOLD)),
+            transform: Transform::from_translation(STAR_CENTER.extend(0.0)),
+            ..default()
+        })
+        .insert(StarMesh);
+}
+
+fn change_star(keyboard_input: Res<Input<KeyCode>>, mut star_settings: ResMut<StarSettings>) {
+    let mut n = star_settings.n;
+    let mut k = star_settings.k;
+
+    if keyboard_input.just_pressed(KeyCode::Up) {
+        n = (n + 1).min(MAX_STAR_POINTS);
+    }
+    if keyboard_input.just_pressed(KeyCode::Down) {
+        n = n.saturating_sub(1).max(3);
+    }
+    if keyboard_input.just_pressed(KeyCode::Right) {
+        k += 1;
+    }
+    if keyboard_input.just_pressed(KeyCode::Left) {
+        k = k.saturating_sub(1);
+    }
+    // k past n - 1 would only draw the same stars again
+    k = k.clamp(1, n - 1);
+
+    // Only write when something changed, so the star is only rebuilt then
+    if n != star_settings.n || k != star_settings.k {
+        star_settings.n = n;
+        star_settings.k = k;
+    }
+}
+
+fn draw_star(
+    star_settings: Res<StarSettings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    star_query: Query<&Mesh2dHandle, With<StarMesh>>,
+    mut hud: ResMut<Hud>,
+) {
+    if !star_settings.is_changed() {
+        return;
+    }
+
+    let StarSettings { n, k } = *star_settings;
+    for mesh_handle in star_query.iter() {
+        if let Some(mesh) = meshes.get_mut(&mesh_handle.0) {
+            *mesh = star_polygon_outline(
+                n,
+                k,
+                STAR_RADIUS,
+                ROTATION,
+                OUTLINE_WIDTH / 2.0,
+                LineJoin::Miter { limit: 4.0 },
+            )
+            .into_mesh();
+        }
+    }
+
+    let loops = star_polygon_loops(n, k, STAR_RADIUS, ROTATION).len();
+    hud.set(
+        "star",
+        format!("{{{}/{}}}, {} loop(s), arrow keys to change", n, k, loops),
+    );
+}
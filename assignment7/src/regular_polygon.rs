@@ -0,0 +1,288 @@
+use bevy::prelude::*;
+use mathcore::mesh::{LineJoin, MeshBuffers};
+use std::f32::consts::TAU;
+
+// The corners of a regular polygon around the origin, going counter clockwise.
+// The first corner is `rotation` radians from the x axis.
+// Panics with fewer than 3 sides, that isn't a polygon.
+pub fn regular_polygon_points(sides: u32, radius: f32, rotation: f32) -> Vec<Vec2> {
+    assert!(sides >= 3, "{} sides is not a polygon", sides);
+    let angle_between_points = TAU / sides as f32;
+
+    (0..sides)
+        .map(|point| Vec2::from_angle(rotation + point as f32 * angle_between_points) * radius)
+        .collect()
+}
+
+// The polygon filled in with a fan of triangles from the first corner, sides - 2 triangles.
+pub fn filled_regular_polygon(sides: u32, radius: f32, rotation: f32) -> MeshBuffers {
+    let mut buffers = MeshBuffers::new();
+    buffers.push_convex_polygon(&regular_polygon_points(sides, radius, rotation));
+    buffers
+}
+
+// Only the edges, `width` thick and centered on them.
+pub fn regular_polygon_outline(
+    sides: u32,
+    radius: f32,
+    rotation: f32,
+    width: f32,
+    join: LineJoin,
+) -> MeshBuffers {
+    let mut buffers = MeshBuffers::new();
+    buffers.push_closed_stroke(
+        &regular_polygon_points(sides, radius, rotation),
+        width,
+        join,
+    );
+    buffers
+}
+
+fn greatest_common_divisor(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        greatest_common_divisor(b, a % b)
+    }
+}
+
+// The star polygon {n/k} in Schläfli notation, n corners of a regular polygon with every k-th
+// corner connected. When n and k share a divisor the lines close up before visiting every corner,
+// so {6/2} is two triangles, and it takes gcd(n, k) separate loops to visit all of them.
+// {n/k} is the same shape as {n/(n - k)}, and when k is half of n every loop is a single line.
+// There are no loops when k is a multiple of n, and n has to be at least 3.
+pub fn star_polygon_loops(n: u32, k: u32, radius: f32, rotation: f32) -> Vec<Vec<Vec2>> {
+    let corners = regular_polygon_points(n, radius, rotation);
+    let k = k % n;
+    if k == 0 {
+        return Vec::new();
+    }
+    let k = k.min(n - k);
+
+    let loop_count = greatest_common_divisor(n, k);
+    (0..loop_count)
+        .map(|start| {
+            (0..n / loop_count)
+                .map(|step| corners[((start + step * k) % n) as usize])
+                .collect()
+        })
+        .collect()
+}
+
+// Every loop of the star polygon as a stroke `width` thick.
+pub fn star_polygon_outline(
+    n: u32,
+    k: u32,
+    radius: f32,
+    rotation: f32,
+    width: f32,
+    join: LineJoin,
+) -> MeshBuffers {
+    let mut buffers = MeshBuffers::new();
+    for points in star_polygon_loops(n, k, radius, rotation) {
+        if let [start, end] = points[..] {
+            buffers.push_line(start, end, width);
+        } else {
+            buffers.push_closed_stroke(&points, width, join);
+        }
+    }
+    buffers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangles(buffers: &MeshBuffers) -> Vec<[Vec2; 3]> {
+        buffers
+            .indices
+            .chunks(3)
+            .map(|triangle| [0, 1, 2].map(|i| buffers.positions[triangle[i] as usize]))
+            .collect()
+    }
+
+    fn assert_counter_clockwise(buffers: &MeshBuffers) {
+        for [a, b, c] in triangles(buffers) {
+            assert!(
+                (b - a).perp_dot(c - a) > 0.0,
+                "{} {} {} is clockwise",
+                a,
+                b,
+                c
+            );
+        }
+    }
+
+    fn area(buffers: &MeshBuffers) -> f32 {
+        triangles(buffers)
+            .iter()
+            .map(|[a, b, c]| (*b - *a).perp_dot(*c - *a) / 2.0)
+            .sum()
+    }
+
+    fn polygon_area(sides: u32, radius: f32) -> f32 {
+        sides as f32 * radius * radius * (TAU / sides as f32).sin() / 2.0
+    }
+
+    #[test]
+    fn points() {
+        let points = regular_polygon_points(4, 10.0, 0.5);
+        assert_eq!(points.len(), 4);
+        assert!(points[0].abs_diff_eq(Vec2::from_angle(0.5) * 10.0, 1e-5));
+        for (i, point) in points.iter().enumerate() {
+            assert!((point.length() - 10.0).abs() < 1e-4);
+            // Every side is as long as the others
+            let side = point.distance(points[(i + 1) % 4]);
+            assert!((side - 10.0 * 2.0f32.sqrt()).abs() < 1e-4);
+        }
+    }
+
+    // Fewer than 3 sides isn't a polygon
+    #[test]
+    #[should_panic]
+    fn two_sides() {
+        regular_polygon_points(2, 10.0, 0.0);
+    }
+
+    #[test]
+    fn filled() {
+        for sides in 3..=12 {
+            for rotation in [0.0, 0.3, -2.0] {
+                let buffers = filled_regular_polygon(sides, 50.0, rotation);
+                assert_eq!(buffers.positions.len(), sides as usize);
+                assert_eq!(buffers.triangle_count(), sides as usize - 2);
+                assert!(buffers
+                    .indices
+                    .iter()
+                    .all(|&index| (index as usize) < buffers.positions.len()));
+                assert_counter_clockwise(&buffers);
+                assert!((area(&buffers) - polygon_area(sides, 50.0)).abs() < 1e-2);
+            }
+        }
+    }
+
+    #[test]
+    fn mitered_outline() {
+        for sides in 3..=12 {
+            let buffers =
+                regular_polygon_outline(sides, 50.0, 0.2, 4.0, LineJoin::Miter { limit: 4.0 });
+            // Two vertices at every corner and a quad along every side
+            assert_eq!(buffers.positions.len(), 2 * sides as usize);
+            assert_eq!(buffers.triangle_count(), 2 * sides as usize);
+            assert_counter_clockwise(&buffers);
+
+            // The ring between polygons 2 wider and 2 narrower, measured from the middle of the sides
+            let apothem_scale = 1.0 / (TAU / (2.0 * sides as f32)).cos();
+            let outer = polygon_area(sides, 50.0 + 2.0 * apothem_scale);
+            let inner = polygon_area(sides, 50.0 - 2.0 * apothem_scale);
+            assert!((area(&buffers) - (outer - inner)).abs() < 1e-1);
+        }
+    }
+
+    #[test]
+    fn beveled_outline() {
+        for sides in 3..=12 {
+            let buffers = regular_polygon_outline(sides, 50.0, 0.2, 4.0, LineJoin::Bevel);
+            // Three vertices at every corner, a quad along every side and a triangle at every corner
+            assert_eq!(buffers.positions.len(), 3 * sides as usize);
+            assert_eq!(buffers.triangle_count(), 3 * sides as usize);
+            assert_counter_clockwise(&buffers);
+
+            let mitered =
+                regular_polygon_outline(sides, 50.0, 0.2, 4.0, LineJoin::Miter { limit: 4.0 });
+            assert!(area(&buffers) < area(&mitered));
+        }
+
+        // A triangle's corners reach 2 half widths out, a limit of 1.5 bevels them
+        let buffers = regular_polygon_outline(3, 50.0, 0.0, 4.0, LineJoin::Miter { limit: 1.5 });
+        assert_eq!(buffers.triangle_count(), 9);
+    }
+
+    fn contains(points: &[Vec2], point: Vec2) -> bool {
+        points.iter().any(|other| other.abs_diff_eq(point, 1e-4))
+    }
+
+    #[test]
+    fn pentagram() {
+        let loops = star_polygon_loops(5, 2, 10.0, 0.0);
+        assert_eq!(loops.len(), 1);
+        let corners = regular_polygon_points(5, 10.0, 0.0);
+        // Every corner once, skipping one each time
+        for (i, point) in loops[0].iter().enumerate() {
+            assert!(point.abs_diff_eq(corners[(i * 2) % 5], 1e-5));
+        }
+
+        // A sharp star still has every corner mitered and every triangle counter clockwise
+        let buffers = star_polygon_outline(5, 2, 50.0, 0.0, 4.0, LineJoin::Miter { limit: 4.0 });
+        assert_eq!(buffers.triangle_count(), 10);
+        assert_counter_clockwise(&buffers);
+    }
+
+    #[test]
+    fn compound_stars() {
+        // {6/2} is two triangles
+        let loops = star_polygon_loops(6, 2, 10.0, 0.0);
+        assert_eq!(loops.len(), 2);
+        let corners = regular_polygon_points(6, 10.0, 0.0);
+        for (start, points) in loops.iter().enumerate() {
+            assert_eq!(points.len(), 3);
+            for i in 0..3 {
+                assert!(contains(points, corners[start + 2 * i]));
+            }
+        }
+        let buffers = star_polygon_outline(6, 2, 50.0, 0.0, 4.0, LineJoin::Bevel);
+        assert_eq!(buffers.triangle_count(), 2 * 3 * 3);
+        assert_counter_clockwise(&buffers);
+
+        // {6/3} is three lines through the middle
+        let loops = star_polygon_loops(6, 3, 10.0, 0.0);
+        assert_eq!(loops.len(), 3);
+        for points in loops.iter() {
+            assert_eq!(points.len(), 2);
+            assert!((points[0] + points[1]).abs_diff_eq(Vec2::ZERO, 1e-4));
+        }
+        let buffers = star_polygon_outline(6, 3, 50.0, 0.0, 4.0, LineJoin::Bevel);
+        assert_eq!(buffers.triangle_count(), 3 * 2);
+
+        // {12/4} is four triangles, every corner in exactly one of them
+        let loops = star_polygon_loops(12, 4, 10.0, 0.3);
+        assert_eq!(loops.len(), 4);
+        let corners = regular_polygon_points(12, 10.0, 0.3);
+        for corner in corners {
+            let count = loops
+                .iter()
+                .filter(|points| contains(points, corner))
+                .count();
+            assert_eq!(count, 1);
+        }
+    }
+
+    #[test]
+    fn same_stars() {
+        // {7/4} goes the other way around {7/3}, {5/1} is the pentagon and {5/5} is nothing
+        let lines = |loops: Vec<Vec<Vec2>>| -> Vec<(Vec2, Vec2)> {
+            loops
+                .iter()
+                .flat_map(|points| {
+                    (0..points.len()).map(|i| (points[i], points[(i + 1) % points.len()]))
+                })
+                .collect()
+        };
+        let seven_three = lines(star_polygon_loops(7, 3, 10.0, 0.0));
+        let seven_four = lines(star_polygon_loops(7, 4, 10.0, 0.0));
+        assert_eq!(seven_three.len(), 7);
+        for (start, end) in seven_four {
+            assert!(seven_three.iter().any(|(a, b)| {
+                (a.abs_diff_eq(start, 1e-4) && b.abs_diff_eq(end, 1e-4))
+                    || (a.abs_diff_eq(end, 1e-4) && b.abs_diff_eq(start, 1e-4))
+            }));
+        }
+
+        assert_eq!(
+            star_polygon_loops(5, 1, 10.0, 0.0),
+            vec![regular_polygon_points(5, 10.0, 0.0)]
+        );
+        assert!(star_polygon_loops(5, 5, 10.0, 0.0).is_empty());
+        assert!(star_polygon_loops(5, 0, 10.0, 0.0).is_empty());
+    }
+}
@@ -0,0 +1,265 @@
+use bevy::math::Vec2;
+
+// Point in shape tests. Every shape gets its own function and points exactly on the border count as inside.
+
+// A circle is just the distance from the center compared to the radius.
+pub fn point_in_circle(point: Vec2, center: Vec2, radius: f32) -> bool {
+    (point - center).length_squared() <= radius * radius
+}
+
+// An axis aligned box is inside when the point is between the min and max on both axes.
+// half_extents is half of the width and height of the box.
+pub fn point_in_aabb(point: Vec2, center: Vec2, half_extents: Vec2) -> bool {
+    let offset = (point - center).abs();
+    offset.x <= half_extents.x && offset.y <= half_extents.y
+}
+
+// An oriented box is an axis aligned box that is rotated by `rotation` radians around its center.
+// Rotate the point by the opposite angle so it is in the box's local space, then it is just an aabb test.
+pub fn point_in_obb(point: Vec2, center: Vec2, half_extents: Vec2, rotation: f32) -> bool {
+    let local_point = Vec2::from_angle(-rotation).rotate(point - center);
+    point_in_aabb(local_point, Vec2::ZERO, half_extents)
+}
+
+// A convex polygon is inside when the point is on the same side of every edge.
+// The points can be in either winding order, but the polygon has to be convex.
+pub fn point_in_convex_polygon(point: Vec2, polygon: &[Vec2]) -> bool {
+    if polygon.len() < 3 {
+        return false;
+    }
+
+    let mut has_left = false;
+    let mut has_right = false;
+    for (i, start) in polygon.iter().enumerate() {
+        let end = polygon[(i + 1) % polygon.len()];
+        // The 2D cross product is positive when the point is left of the edge
+        let side = (end - *start).perp_dot(point - *start);
+        has_left |= side > 0.0;
+        has_right |= side < 0.0;
+    }
+
+    !(has_left && has_right)
+}
+
+// A view cone starts at `origin`, points along `heading` and opens `half_angle` radians to both sides.
+// The point is in it when it is at most `max_range` away and the angle between the heading and the
+// direction to the point is at most the half angle. Comparing the dot product of the two normalized
+// vectors with the cosine of the half angle does that without any inverse trig.
+pub fn point_in_view_cone(
+    point: Vec2,
+    origin: Vec2,
+    heading: Vec2,
+    half_angle: f32,
+    max_range: f32,
+) -> bool {
+    let to_point = point - origin;
+    let distance = to_point.length();
+    if distance > max_range {
+        return false;
+    }
+    if distance == 0.0 {
+        return true;
+    }
+
+    heading.normalize_or_zero().dot(to_point / distance) >= half_angle.cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HALF: Vec2 = Vec2::new(50.0, 50.0);
+    const NUDGE: f32 = 0.01;
+
+    #[test]
+    fn circle_border_is_inside() {
+        assert!(point_in_circle(Vec2::new(0.0, 10.0), Vec2::ZERO, 10.0));
+        assert!(!point_in_circle(
+            Vec2::new(0.0, 10.0 + NUDGE),
+            Vec2::ZERO,
+            10.0
+        ));
+    }
+
+    #[test]
+    fn aabb_corners_are_inside() {
+        for corner in [
+            Vec2::new(50.0, 50.0),
+            Vec2::new(-50.0, 50.0),
+            Vec2::new(50.0, -50.0),
+            Vec2::new(-50.0, -50.0),
+        ] {
+            assert!(point_in_aabb(corner, Vec2::ZERO, HALF));
+            assert!(!point_in_aabb(corner * (1.0 + NUDGE), Vec2::ZERO, HALF));
+        }
+    }
+
+    #[test]
+    fn aabb_edges() {
+        let center = Vec2::new(100.0, -20.0);
+        for edge in [Vec2::X, -Vec2::X, Vec2::Y, -Vec2::Y] {
+            let on_edge = center + edge * HALF;
+            assert!(point_in_aabb(on_edge, center, HALF));
+            assert!(!point_in_aabb(on_edge + edge * NUDGE, center, HALF));
+        }
+    }
+
+    #[test]
+    fn aabb_uses_both_extents() {
+        let half = Vec2::new(100.0, 10.0);
+        assert!(point_in_aabb(Vec2::new(90.0, 0.0), Vec2::ZERO, half));
+        assert!(!point_in_aabb(Vec2::new(0.0, 20.0), Vec2::ZERO, half));
+    }
+
+    #[test]
+    fn obb_without_rotation_matches_aabb() {
+        for x in -60..=60 {
+            for y in -60..=60 {
+                let point = Vec2::new(x as f32 * 1.5, y as f32 * 1.5);
+                assert_eq!(
+                    point_in_obb(point, Vec2::ZERO, HALF, 0.0),
+                    point_in_aabb(point, Vec2::ZERO, HALF)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn obb_corners_follow_rotation() {
+        let rotation = f32::to_radians(30.0);
+        let center = Vec2::new(-40.0, 25.0);
+        let axis = Vec2::from_angle(rotation);
+
+        for local_corner in [
+            Vec2::new(50.0, 50.0),
+            Vec2::new(-50.0, 50.0),
+            Vec2::new(50.0, -50.0),
+            Vec2::new(-50.0, -50.0),
+        ] {
+            let just_inside = center + axis.rotate(local_corner * (1.0 - NUDGE));
+            let just_outside = center + axis.rotate(local_corner * (1.0 + NUDGE));
+            assert!(point_in_obb(just_inside, center, HALF, rotation));
+            assert!(!point_in_obb(just_outside, center, HALF, rotation));
+        }
+    }
+
+    #[test]
+    fn obb_edges_follow_rotation() {
+        let rotation = f32::to_radians(45.0);
+        let axis = Vec2::from_angle(rotation);
+
+        for edge in [Vec2::X, -Vec2::X, Vec2::Y, -Vec2::Y] {
+            let on_edge = axis.rotate(edge * HALF);
+            assert!(point_in_obb(
+                on_edge * (1.0 - NUDGE),
+                Vec2::ZERO,
+                HALF,
+                rotation
+            ));
+            assert!(!point_in_obb(
+                on_edge * (1.0 + NUDGE),
+                Vec2::ZERO,
+                HALF,
+                rotation
+            ));
+        }
+
+        // The unrotated corner sticks out of a box turned 45 degrees
+        assert!(point_in_aabb(Vec2::new(49.0, 49.0), Vec2::ZERO, HALF));
+        assert!(!point_in_obb(
+            Vec2::new(49.0, 49.0),
+            Vec2::ZERO,
+            HALF,
+            rotation
+        ));
+    }
+
+    #[test]
+    fn convex_polygon_any_winding() {
+        let triangle = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(10.0, 0.0),
+            Vec2::new(0.0, 10.0),
+        ];
+        let reversed = [triangle[2], triangle[1], triangle[0]];
+
+        for polygon in [&triangle[..], &reversed[..]] {
+            assert!(point_in_convex_polygon(Vec2::new(2.0, 2.0), polygon));
+            // Corners and edges are inside
+            assert!(point_in_convex_polygon(Vec2::new(10.0, 0.0), polygon));
+            assert!(point_in_convex_polygon(Vec2::new(5.0, 5.0), polygon));
+            assert!(!point_in_convex_polygon(
+                Vec2::new(5.0 + NUDGE, 5.0 + NUDGE),
+                polygon
+            ));
+            assert!(!point_in_convex_polygon(Vec2::new(-NUDGE, 5.0), polygon));
+        }
+    }
+
+    #[test]
+    fn convex_polygon_matches_aabb() {
+        let square = [
+            Vec2::new(-50.0, -50.0),
+            Vec2::new(50.0, -50.0),
+            Vec2::new(50.0, 50.0),
+            Vec2::new(-50.0, 50.0),
+        ];
+        for x in -60..=60 {
+            for y in -60..=60 {
+                let point = Vec2::new(x as f32 * 1.5, y as f32 * 1.5);
+                assert_eq!(
+                    point_in_convex_polygon(point, &square),
+                    point_in_aabb(point, Vec2::ZERO, HALF)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn degenerate_polygon_is_empty() {
+        assert!(!point_in_convex_polygon(Vec2::ZERO, &[Vec2::ZERO, Vec2::X]));
+    }
+
+    #[test]
+    fn view_cone() {
+        let half_angle = f32::to_radians(30.0);
+        let origin = Vec2::new(10.0, 10.0);
+        let heading = Vec2::Y * 5.0;
+        let inside = |point: Vec2| point_in_view_cone(point, origin, heading, half_angle, 100.0);
+
+        assert!(inside(origin + Vec2::new(0.0, 50.0)));
+        // Behind, out of range and just past the side of the cone
+        assert!(!inside(origin - Vec2::new(0.0, 50.0)));
+        assert!(!inside(origin + Vec2::new(0.0, 100.0 + NUDGE)));
+        let side = Vec2::from_angle(f32::to_radians(90.0 + 30.0));
+        assert!(inside(
+            origin + Vec2::from_angle(f32::to_radians(90.0 + 29.9)) * 50.0
+        ));
+        assert!(!inside(
+            origin + Vec2::from_angle(f32::to_radians(90.0 + 30.1)) * 50.0
+        ));
+        assert!(!inside(origin + side.perp() * 50.0));
+    }
+
+    #[test]
+    fn view_cone_ignores_position_from_origin() {
+        // The old test compared directions from the world origin, this one has to use the heading
+        let heading = Vec2::new(-1.0, 0.0);
+        let origin = Vec2::new(120.0, 0.0);
+        let target = Vec2::new(-50.0, 100.0);
+        assert!(point_in_view_cone(
+            target,
+            origin,
+            heading,
+            f32::to_radians(45.0),
+            500.0
+        ));
+        assert!(!point_in_view_cone(
+            target,
+            origin,
+            -heading,
+            f32::to_radians(45.0),
+            500.0
+        ));
+    }
+}
@@ -0,0 +1,63 @@
+use bevy::prelude::*;
+use bevy::render::camera::RenderTarget;
+
+// Where the mouse is in world space, for a 2D camera.
+// Bevy gives the cursor in window pixels, measured from the bottom left corner.
+// Dividing by the window size and mapping to -1..1 gives normalized device coordinates,
+// and undoing the projection and then the camera transform gives the world position.
+pub fn screen_to_world(
+    cursor_position: Vec2,
+    window_size: Vec2,
+    camera_transform: &GlobalTransform,
+    projection_matrix: Mat4,
+) -> Vec2 {
+    let ndc = (cursor_position / window_size) * 2.0 - Vec2::ONE;
+    let ndc_to_world = camera_transform.compute_matrix() * projection_matrix.inverse();
+    ndc_to_world.project_point3(ndc.extend(-1.0)).truncate()
+}
+
+// The world position of the cursor as seen by `camera`,
+// None when the cursor is outside the camera's window.
+pub fn cursor_world_position(
+    windows: &Windows,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Option<Vec2> {
+    let window = match camera.target {
+        RenderTarget::Window(id) => windows.get(id)?,
+        RenderTarget::Image(_) => return None,
+    };
+    let cursor_position = window.cursor_position()?;
+    let window_size = Vec2::new(window.width(), window.height());
+
+    Some(screen_to_world(
+        cursor_position,
+        window_size,
+        camera_transform,
+        camera.projection_matrix(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-3;
+
+    #[test]
+    fn cursor_to_world() {
+        let window_size = Vec2::new(800.0, 600.0);
+        // What a Camera2dBundle uses for a window this size
+        let projection = Mat4::orthographic_rh(-400.0, 400.0, -300.0, 300.0, 0.0, 1000.0);
+        let camera = GlobalTransform::from(Transform::from_xyz(100.0, -50.0, 999.9));
+
+        let center = screen_to_world(window_size / 2.0, window_size, &camera, projection);
+        assert!(center.distance(Vec2::new(100.0, -50.0)) < EPSILON);
+
+        let bottom_left = screen_to_world(Vec2::ZERO, window_size, &camera, projection);
+        assert!(bottom_left.distance(Vec2::new(-300.0, -350.0)) < EPSILON);
+
+        let top_right = screen_to_world(window_size, window_size, &camera, projection);
+        assert!(top_right.distance(Vec2::new(500.0, 250.0)) < EPSILON);
+    }
+}
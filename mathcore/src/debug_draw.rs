@@ -0,0 +1,386 @@
+use bevy::prelude::*;
+use bevy::sprite::MaterialMesh2dBundle;
+use bevy::utils::HashMap;
+
+use crate::font::{EmbeddedFont, EmbeddedFontPlugin};
+use crate::mesh::MeshBuffers;
+
+// Immediate mode debug drawing. Any system can ask the DebugDraw resource for lines, arrows,
+// circles and text, they are drawn at the end of the frame and then forgotten,
+// unless they were given a duration with `lasting`.
+pub struct DebugDrawPlugin;
+
+impl Plugin for DebugDrawPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<EmbeddedFont>() {
+            app.add_plugin(EmbeddedFontPlugin);
+        }
+
+        app.init_resource::<DebugDraw>()
+            // PostUpdate so everything the normal systems asked for this frame is there
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                draw_debug_shapes.label(DebugDrawSystem),
+            );
+    }
+}
+
+// Systems that draw in PostUpdate themselves, for example after the transforms were propagated,
+// should run `.before(DebugDrawSystem)` so their shapes show up the same frame.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub struct DebugDrawSystem;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DebugPrimitive {
+    Line {
+        start: Vec2,
+        end: Vec2,
+        color: Color,
+    },
+    Arrow {
+        start: Vec2,
+        end: Vec2,
+        color: Color,
+    },
+    // An arrow starting at origin, the length of the direction is the length of the arrow
+    Ray {
+        origin: Vec2,
+        direction: Vec2,
+        color: Color,
+    },
+    Circle {
+        center: Vec2,
+        radius: f32,
+        color: Color,
+    },
+    Text {
+        position: Vec2,
+        text: String,
+        color: Color,
+    },
+}
+
+impl DebugPrimitive {
+    pub fn color(&self) -> Color {
+        match self {
+            DebugPrimitive::Line { color, .. }
+            | DebugPrimitive::Arrow { color, .. }
+            | DebugPrimitive::Ray { color, .. }
+            | DebugPrimitive::Circle { color, .. }
+            | DebugPrimitive::Text { color, .. } => *color,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugShape {
+    pub primitive: DebugPrimitive,
+    // Seconds the shape is still drawn for, a shape with no time left is drawn for one more frame
+    pub time_left: f32,
+}
+
+impl DebugShape {
+    // Keep drawing the shape for this many seconds instead of only this frame.
+    pub fn lasting(&mut self, seconds: f32) -> &mut Self {
+        self.time_left = seconds;
+        self
+    }
+}
+
+pub struct DebugDraw {
+    // How thick lines, arrows and circles are in pixels
+    pub line_width: f32,
+    pub font_size: f32,
+    // Draw on top of everything else
+    pub z: f32,
+    shapes: Vec<DebugShape>,
+}
+
+impl Default for DebugDraw {
+    fn default() -> Self {
+        DebugDraw {
+            line_width: 2.0,
+            font_size: 16.0,
+            z: 100.0,
+            shapes: Vec::new(),
+        }
+    }
+}
+
+impl DebugDraw {
+    fn add(&mut self, primitive: DebugPrimitive) -> &mut DebugShape {
+        self.shapes.push(DebugShape {
+            primitive,
+            time_left: 0.0,
+        });
+        self.shapes.last_mut().unwrap()
+    }
+
+    pub fn line(&mut self, start: Vec2, end: Vec2, color: Color) -> &mut DebugShape {
+        self.add(DebugPrimitive::Line { start, end, color })
+    }
+
+    pub fn arrow(&mut self, start: Vec2, end: Vec2, color: Color) -> &mut DebugShape {
+        self.add(DebugPrimitive::Arrow { start, end, color })
+    }
+
+    pub fn ray(&mut self, origin: Vec2, direction: Vec2, color: Color) -> &mut DebugShape {
+        self.add(DebugPrimitive::Ray {
+            origin,
+            direction,
+            color,
+        })
+    }
+
+    pub fn circle(&mut self, center: Vec2, radius: f32, color: Color) -> &mut DebugShape {
+        self.add(DebugPrimitive::Circle {
+            center,
+            radius,
+            color,
+        })
+    }
+
+    pub fn text_label(
+        &mut self,
+        position: Vec2,
+        text: impl Into<String>,
+        color: Color,
+    ) -> &mut DebugShape {
+        self.add(DebugPrimitive::Text {
+            position,
+            text: text.into(),
+            color,
+        })
+    }
+
+    // Everything that will be drawn this frame.
+    pub fn primitives(&self) -> impl Iterator<Item = &DebugPrimitive> {
+        self.shapes.iter().map(|shape| &shape.primitive)
+    }
+
+    pub fn clear(&mut self) {
+        self.shapes.clear();
+    }
+
+    // Called after drawing a frame, forget the shapes that ran out of time.
+    pub fn tick(&mut self, delta_seconds: f32) {
+        self.shapes.retain_mut(|shape| {
+            shape.time_left -= delta_seconds;
+            shape.time_left > 0.0
+        });
+    }
+
+    // Turn every shape except text into triangles, with one set of buffers per color
+    // so each color can be drawn as a single mesh.
+    pub fn mesh_batches(&self) -> Vec<(Color, MeshBuffers)> {
+        let mut batches: Vec<(Color, MeshBuffers)> = Vec::new();
+
+        for primitive in self.primitives() {
+            let color = primitive.color();
+            let index = match batches
+                .iter()
+                .position(|(batch_color, _)| *batch_color == color)
+            {
+                Some(index) => index,
+                None => {
+                    batches.push((color, MeshBuffers::new()));
+                    batches.len() - 1
+                }
+            };
+            let buffers = &mut batches[index].1;
+
+            match primitive {
+                DebugPrimitive::Line { start, end, .. } => {
+                    buffers.push_line(*start, *end, self.line_width);
+                }
+                DebugPrimitive::Arrow { start, end, .. } => {
+                    push_arrow(buffers, *start, *end, self.line_width);
+                }
+                DebugPrimitive::Ray {
+                    origin, direction, ..
+                } => {
+                    push_arrow(buffers, *origin, *origin + *direction, self.line_width);
+                }
+                DebugPrimitive::Circle { center, radius, .. } => {
+                    buffers.push_circle_outline(*center, *radius, self.line_width, 32);
+                }
+                DebugPrimitive::Text { .. } => {}
+            }
+        }
+
+        batches.retain(|(_, buffers)| !buffers.indices.is_empty());
+        batches
+    }
+}
+
+// An arrow is a line with a triangle at the end. The head gets bigger with the line width
+// but is never more than half of the arrow.
+fn push_arrow(buffers: &mut MeshBuffers, start: Vec2, end: Vec2, width: f32) {
+    let length = start.distance(end);
+    if length == 0.0 {
+        return;
+    }
+
+    let direction = (end - start) / length;
+    let head_length = (width * 5.0).max(8.0).min(length / 2.0);
+    let head_base = end - direction * head_length;
+    let head_side = direction.perp() * head_length / 2.0;
+
+    buffers.push_line(start, head_base, width);
+    buffers.push_triangle(end, head_base + head_side, head_base - head_side);
+}
+
+// The entities showing the debug shapes, kept around between frames.
+// There is one mesh per color that gets replaced every frame, text gets respawned every frame.
+#[derive(Default)]
+pub struct DebugDrawEntities {
+    batches: HashMap<u32, (Entity, Handle<Mesh>)>,
+    labels: Vec<Entity>,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn draw_debug_shapes(
+    mut commands: Commands,
+    mut debug_draw: ResMut<DebugDraw>,
+    mut entities: Local<DebugDrawEntities>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut visibility_query: Query<&mut Visibility>,
+    font: Res<EmbeddedFont>,
+    time: Res<Time>,
+) {
+    let batches = debug_draw.mesh_batches();
+
+    // Hide the colors that are not used this frame
+    for (key, (entity, _)) in entities.batches.iter() {
+        if let Ok(mut visibility) = visibility_query.get_mut(*entity) {
+            visibility.is_visible = batches.iter().any(|(color, _)| color.as_rgba_u32() == *key);
+        }
+    }
+
+    for (color, buffers) in batches {
+        let key = color.as_rgba_u32();
+        if let Some((_, handle)) = entities.batches.get(&key) {
+            if let Some(mesh) = meshes.get_mut(handle) {
+                *mesh = buffers.into_mesh();
+            }
+        } else {
+            let handle = meshes.add(buffers.into_mesh());
+            let entity = commands
+                .spawn_bundle(MaterialMesh2dBundle {
+                    mesh: handle.clone().into(),
+                    material: materials.add(ColorMaterial::from(color)),
+                    transform: Transform::from_xyz(0.0, 0.0, debug_draw.z),
+                    ..default()
+                })
+                .id();
+            entities.batches.insert(key, (entity, handle));
+        }
+    }
+
+    for entity in entities.labels.drain(..) {
+        commands.entity(entity).despawn();
+    }
+
+    let mut labels = Vec::new();
+    for primitive in debug_draw.primitives() {
+        if let DebugPrimitive::Text {
+            position,
+            text,
+            color,
+        } = primitive
+        {
+            let style = TextStyle {
+                font: font.0.clone(),
+                font_size: debug_draw.font_size,
+                color: *color,
+            };
+            let entity = commands
+                .spawn_bundle(Text2dBundle {
+                    text: Text::from_section(text.clone(), style)
+                        .with_alignment(TextAlignment::CENTER),
+                    transform: Transform::from_translation(position.extend(debug_draw.z)),
+                    ..default()
+                })
+                .id();
+            labels.push(entity);
+        }
+    }
+    entities.labels = labels;
+
+    debug_draw.tick(time.delta_seconds());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shapes_last_one_frame() {
+        let mut debug_draw = DebugDraw::default();
+        debug_draw.arrow(Vec2::ZERO, Vec2::X * 100.0, Color::RED);
+        debug_draw.text_label(Vec2::ZERO, "origin", Color::WHITE);
+
+        assert_eq!(
+            debug_draw.primitives().next(),
+            Some(&DebugPrimitive::Arrow {
+                start: Vec2::ZERO,
+                end: Vec2::X * 100.0,
+                color: Color::RED
+            })
+        );
+        assert_eq!(debug_draw.primitives().count(), 2);
+
+        debug_draw.tick(1.0 / 60.0);
+        assert_eq!(debug_draw.primitives().count(), 0);
+    }
+
+    #[test]
+    fn lasting_shapes() {
+        let mut debug_draw = DebugDraw::default();
+        debug_draw
+            .circle(Vec2::ZERO, 10.0, Color::GREEN)
+            .lasting(0.5);
+        debug_draw.line(Vec2::ZERO, Vec2::Y, Color::GREEN);
+
+        debug_draw.tick(0.25);
+        assert_eq!(debug_draw.primitives().count(), 1);
+        debug_draw.tick(0.25);
+        assert_eq!(debug_draw.primitives().count(), 0);
+    }
+
+    #[test]
+    fn batches_by_color() {
+        let mut debug_draw = DebugDraw::default();
+        debug_draw.line(Vec2::ZERO, Vec2::X, Color::RED);
+        debug_draw.ray(Vec2::ZERO, Vec2::Y * 50.0, Color::RED);
+        debug_draw.circle(Vec2::ZERO, 10.0, Color::BLUE);
+        debug_draw.text_label(Vec2::ZERO, "not a mesh", Color::GREEN);
+
+        let batches = debug_draw.mesh_batches();
+        assert_eq!(batches.len(), 2);
+
+        let (color, red) = &batches[0];
+        assert_eq!(*color, Color::RED);
+        // A line is two triangles and an arrow is a line and a head
+        assert_eq!(red.triangle_count(), 2 + 3);
+
+        let (color, blue) = &batches[1];
+        assert_eq!(*color, Color::BLUE);
+        assert_eq!(blue.triangle_count(), 32 * 2);
+    }
+
+    #[test]
+    fn arrow_head_ends_at_the_tip() {
+        let mut buffers = MeshBuffers::new();
+        push_arrow(&mut buffers, Vec2::ZERO, Vec2::new(0.0, 100.0), 2.0);
+        assert!(buffers.positions.contains(&Vec2::new(0.0, 100.0)));
+        // Nothing goes past the tip
+        assert!(buffers.positions.iter().all(|point| point.y <= 100.0));
+
+        // An arrow with no length draws nothing
+        let mut buffers = MeshBuffers::new();
+        push_arrow(&mut buffers, Vec2::ONE, Vec2::ONE, 2.0);
+        assert_eq!(buffers.triangle_count(), 0);
+    }
+}
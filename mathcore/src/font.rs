@@ -0,0 +1,27 @@
+use bevy::prelude::*;
+
+// Bevy has no built in font, so one is baked into the crate.
+// That way every assignment can draw text without its own assets folder.
+const FONT_BYTES: &[u8] = include_bytes!("../assets/DejaVuSansMono.ttf");
+
+// Adds the EmbeddedFont resource. Add it after the DefaultPlugins so the font assets exist.
+pub struct EmbeddedFontPlugin;
+
+impl Plugin for EmbeddedFontPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EmbeddedFont>();
+    }
+}
+
+// Handle to the baked in font to use in a TextStyle.
+pub struct EmbeddedFont(pub Handle<Font>);
+
+impl FromWorld for EmbeddedFont {
+    fn from_world(world: &mut World) -> Self {
+        let font = Font::try_from_bytes(FONT_BYTES.to_vec()).expect("The embedded font is broken");
+        let mut fonts = world
+            .get_resource_mut::<Assets<Font>>()
+            .expect("Add the DefaultPlugins before the EmbeddedFontPlugin");
+        EmbeddedFont(fonts.add(font))
+    }
+}
@@ -0,0 +1,359 @@
+use bevy::prelude::*;
+use std::fmt::Write;
+
+use crate::font::{EmbeddedFont, EmbeddedFontPlugin};
+
+// Shows named values in a corner of the screen instead of printing them every frame.
+// Systems write into the Hud resource with `hud.set("dot product", dot_product)`.
+// Press the dump key to write every value to hud_values.csv and hud_values.json.
+pub struct HudPlugin {
+    // Digits after the decimal point
+    pub precision: usize,
+    pub corner: HudCorner,
+    pub dump_key: KeyCode,
+}
+
+impl Default for HudPlugin {
+    fn default() -> Self {
+        HudPlugin {
+            precision: 3,
+            corner: HudCorner::TopLeft,
+            dump_key: KeyCode::F5,
+        }
+    }
+}
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<EmbeddedFont>() {
+            app.add_plugin(EmbeddedFontPlugin);
+        }
+
+        app.insert_resource(Hud {
+            precision: self.precision,
+            corner: self.corner,
+            dump_key: self.dump_key,
+            ..default()
+        })
+        .add_startup_system(add_hud_text)
+        .add_system_to_stage(CoreStage::PostUpdate, update_hud_text)
+        .add_system(dump_hud_values);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HudCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HudValue {
+    Number(f32),
+    Vector(Vec2),
+    Text(String),
+}
+
+impl From<f32> for HudValue {
+    fn from(number: f32) -> Self {
+        HudValue::Number(number)
+    }
+}
+
+impl From<Vec2> for HudValue {
+    fn from(vector: Vec2) -> Self {
+        HudValue::Vector(vector)
+    }
+}
+
+impl From<Vec3> for HudValue {
+    fn from(vector: Vec3) -> Self {
+        HudValue::Vector(vector.truncate())
+    }
+}
+
+impl From<String> for HudValue {
+    fn from(text: String) -> Self {
+        HudValue::Text(text)
+    }
+}
+
+impl From<&str> for HudValue {
+    fn from(text: &str) -> Self {
+        HudValue::Text(text.to_string())
+    }
+}
+
+impl HudValue {
+    pub fn format(&self, precision: usize) -> String {
+        match self {
+            HudValue::Number(number) => format!("{:.*}", precision, number),
+            HudValue::Vector(vector) => {
+                format!("({:.*}, {:.*})", precision, vector.x, precision, vector.y)
+            }
+            HudValue::Text(text) => text.clone(),
+        }
+    }
+}
+
+// Every value stays until it is set again or removed, in the order they were first set.
+pub struct Hud {
+    pub precision: usize,
+    pub corner: HudCorner,
+    pub dump_key: KeyCode,
+    values: Vec<(String, HudValue)>,
+}
+
+// The same settings as the default HudPlugin
+impl Default for Hud {
+    fn default() -> Self {
+        let plugin = HudPlugin::default();
+        Hud {
+            precision: plugin.precision,
+            corner: plugin.corner,
+            dump_key: plugin.dump_key,
+            values: Vec::new(),
+        }
+    }
+}
+
+impl Hud {
+    pub fn set(&mut self, name: &str, value: impl Into<HudValue>) {
+        let value = value.into();
+        match self.values.iter_mut().find(|(key, _)| key == name) {
+            Some((_, old_value)) => *old_value = value,
+            None => self.values.push((name.to_string(), value)),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&HudValue> {
+        self.values
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.values.retain(|(key, _)| key != name);
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = (&str, &HudValue)> {
+        self.values.iter().map(|(key, value)| (key.as_str(), value))
+    }
+
+    // One "name: value" line per value, what the overlay shows
+    pub fn text(&self) -> String {
+        self.values
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name, value.format(self.precision)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    // A header and one row per value. Vectors get an x and y column, the other values leave y empty.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("name,x,y\n");
+        for (name, value) in self.values.iter() {
+            let name = csv_field(name);
+            let _ = match value {
+                HudValue::Number(number) => writeln!(csv, "{},{},", name, number),
+                HudValue::Vector(vector) => writeln!(csv, "{},{},{}", name, vector.x, vector.y),
+                HudValue::Text(text) => writeln!(csv, "{},{},", name, csv_field(text)),
+            };
+        }
+        csv
+    }
+
+    // An object with a key per value. Numbers stay numbers, vectors become [x, y].
+    pub fn to_json(&self) -> String {
+        let fields: Vec<String> = self
+            .values
+            .iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    HudValue::Number(number) => json_number(*number),
+                    HudValue::Vector(vector) => {
+                        format!("[{}, {}]", json_number(vector.x), json_number(vector.y))
+                    }
+                    HudValue::Text(text) => json_string(text),
+                };
+                format!("  {}: {}", json_string(name), value)
+            })
+            .collect();
+
+        if fields.is_empty() {
+            "{}\n".to_string()
+        } else {
+            format!("{{\n{}\n}}\n", fields.join(",\n"))
+        }
+    }
+}
+
+// Quote fields with commas, quotes or new lines in them
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut json = String::from("\"");
+    for character in text.chars() {
+        match character {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            character if character.is_control() => {
+                let _ = write!(json, "\\u{:04x}", character as u32);
+            }
+            character => json.push(character),
+        }
+    }
+    json.push('"');
+    json
+}
+
+// JSON has no NaN or infinity
+fn json_number(number: f32) -> String {
+    if number.is_finite() {
+        number.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+#[derive(Component)]
+struct HudText;
+
+fn add_hud_text(mut commands: Commands, hud: Res<Hud>, font: Res<EmbeddedFont>) {
+    let margin = Val::Px(10.0);
+    let mut position = UiRect::default();
+    match hud.corner {
+        HudCorner::TopLeft => {
+            position.top = margin;
+            position.left = margin;
+        }
+        HudCorner::TopRight => {
+            position.top = margin;
+            position.right = margin;
+        }
+        HudCorner::BottomLeft => {
+            position.bottom = margin;
+            position.left = margin;
+        }
+        HudCorner::BottomRight => {
+            position.bottom = margin;
+            position.right = margin;
+        }
+    }
+
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: font.0.clone(),
+                    font_size: 18.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position,
+                ..default()
+            }),
+        )
+        .insert(HudText);
+}
+
+fn update_hud_text(hud: Res<Hud>, mut text_query: Query<&mut Text, With<HudText>>) {
+    if !hud.is_changed() {
+        return;
+    }
+
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = hud.text();
+    }
+}
+
+fn dump_hud_values(keyboard_input: Res<Input<KeyCode>>, hud: Res<Hud>) {
+    if !keyboard_input.just_pressed(hud.dump_key) {
+        return;
+    }
+
+    for (path, contents) in [
+        ("hud_values.csv", hud.to_csv()),
+        ("hud_values.json", hud.to_json()),
+    ] {
+        match std::fs::write(path, contents) {
+            Ok(()) => info!("Wrote the HUD values to {}", path),
+            Err(error) => error!("Could not write the HUD values to {}: {}", path, error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hud() -> Hud {
+        Hud {
+            precision: 2,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn values_keep_their_order() {
+        let mut hud = hud();
+        hud.set("dot product", 0.5);
+        hud.set("hit normal", Vec2::new(0.0, -1.0));
+        hud.set("dot product", 0.25);
+
+        assert_eq!(hud.get("dot product"), Some(&HudValue::Number(0.25)));
+        assert_eq!(hud.text(), "dot product: 0.25\nhit normal: (0.00, -1.00)");
+
+        hud.remove("dot product");
+        assert_eq!(hud.values().count(), 1);
+    }
+
+    #[test]
+    fn precision() {
+        let mut hud = hud();
+        hud.set("angle", std::f32::consts::PI);
+        assert_eq!(hud.text(), "angle: 3.14");
+        hud.precision = 4;
+        assert_eq!(hud.text(), "angle: 3.1416");
+    }
+
+    #[test]
+    fn csv() {
+        let mut hud = hud();
+        hud.set("magnitude", 2.5);
+        hud.set("ray vec", Vec2::new(80.0, -80.0));
+        hud.set("mode, name", "say \"hi\"");
+        assert_eq!(
+            hud.to_csv(),
+            "name,x,y\nmagnitude,2.5,\nray vec,80,-80\n\"mode, name\",\"say \"\"hi\"\"\",\n"
+        );
+    }
+
+    #[test]
+    fn json() {
+        let mut hud = hud();
+        assert_eq!(hud.to_json(), "{}\n");
+
+        hud.set("magnitude", 2.5);
+        hud.set("ray vec", Vec2::new(80.0, -80.0));
+        hud.set("mode", "a \"quote\"");
+        hud.set("broken", f32::NAN);
+        assert_eq!(
+            hud.to_json(),
+            "{\n  \"magnitude\": 2.5,\n  \"ray vec\": [80, -80],\n  \"mode\": \"a \\\"quote\\\"\",\n  \"broken\": null\n}\n"
+        );
+    }
+}
@@ -0,0 +1,457 @@
+use bevy::prelude::*;
+use std::f32::consts::PI;
+
+// Inverse kinematics, finding the joint angles that put the end of a chain of bones on a target.
+//
+// A chain starts at `base`. Every angle is relative: the first one is measured from `reference`,
+// the direction the first bone points when its angle is 0, and every other one from the bone before it.
+// That is the same as the rotation of each bone in a parent/child hierarchy.
+
+// The smallest and largest relative angle a joint can bend to, in radians.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JointLimit {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl JointLimit {
+    pub const FREE: JointLimit = JointLimit { min: -PI, max: PI };
+
+    // The same amount to both sides
+    pub fn symmetric(max_angle: f32) -> Self {
+        JointLimit {
+            min: -max_angle,
+            max: max_angle,
+        }
+    }
+
+    pub fn clamp(&self, angle: f32) -> f32 {
+        angle.clamp(self.min, self.max)
+    }
+}
+
+// Which way the middle joint of a two bone chain bends, seen from the base.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BendDirection {
+    Clockwise,
+    CounterClockwise,
+}
+
+// Where every joint ends up for the given angles, starting with the base and ending with the tip.
+pub fn forward_kinematics(
+    base: Vec2,
+    reference: Vec2,
+    lengths: &[f32],
+    angles: &[f32],
+) -> Vec<Vec2> {
+    let mut positions = vec![base];
+    let mut direction = reference.normalize_or_zero();
+    for (length, angle) in lengths.iter().zip(angles) {
+        direction = Vec2::from_angle(*angle).rotate(direction);
+        positions.push(*positions.last().unwrap() + direction * *length);
+    }
+    positions
+}
+
+// The relative angles of a chain given where its joints are, the opposite of `forward_kinematics`.
+pub fn joint_angles(positions: &[Vec2], reference: Vec2) -> Vec<f32> {
+    let mut previous = reference;
+    positions
+        .windows(2)
+        .map(|bone| {
+            let direction = bone[1] - bone[0];
+            let angle = signed_angle(previous, direction);
+            previous = direction;
+            angle
+        })
+        .collect()
+}
+
+// Two bones solved exactly with the law of cosines. The base, the middle joint and the target
+// make a triangle with sides `upper`, `lower` and the distance to the target,
+// so the angle at the middle joint follows from the three sides.
+// Then the whole chain is turned at the base until its tip points at the target.
+// Targets out of reach get the chain stretched out straight towards them.
+// If the limits don't let the middle joint bend the asked way far enough it bends the other way
+// when that reaches, otherwise it stops at the limit with the chain still pointing at the target.
+pub fn two_bone_ik(
+    base: Vec2,
+    reference: Vec2,
+    lengths: [f32; 2],
+    target: Vec2,
+    bend: BendDirection,
+    limits: [JointLimit; 2],
+) -> [f32; 2] {
+    let [upper, lower] = lengths;
+    let to_target = target - base;
+    let target_angle = if to_target == Vec2::ZERO {
+        0.0
+    } else {
+        signed_angle(reference, to_target)
+    };
+
+    // A triangle can't have one side longer than the other two together
+    let distance = to_target
+        .length()
+        .clamp((upper - lower).abs(), upper + lower);
+
+    // Angle inside the triangle at the middle joint, a straight chain has PI here
+    let middle_angle = if upper == 0.0 || lower == 0.0 {
+        PI
+    } else {
+        ((upper * upper + lower * lower - distance * distance) / (2.0 * upper * lower))
+            .clamp(-1.0, 1.0)
+            .acos()
+    };
+
+    let second = match bend {
+        BendDirection::Clockwise => -(PI - middle_angle),
+        BendDirection::CounterClockwise => PI - middle_angle,
+    };
+
+    // When the limit stops the middle joint, bending the other way may still reach the target
+    let second = if limits[1].clamp(second) != second && limits[1].clamp(-second) == -second {
+        -second
+    } else {
+        limits[1].clamp(second)
+    };
+
+    // Turn the whole chain so its tip points at the target
+    let tip = *forward_kinematics(Vec2::ZERO, reference, &lengths, &[0.0, second])
+        .last()
+        .unwrap();
+    let first = target_angle - signed_angle(reference, tip);
+
+    [limits[0].clamp(wrap_angle(first)), second]
+}
+
+// The result of an iterative solve.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IkSolution {
+    pub angles: Vec<f32>,
+    // How far the tip ended up from the target
+    pub distance: f32,
+    pub iterations: usize,
+}
+
+// FABRIK, forward and backward reaching inverse kinematics, for chains of any length.
+// Every iteration drags the chain tip first onto the target, pulling each joint after it,
+// then drags the base back where it belongs the same way. The joint limits are applied
+// on the way back out from the base by turning each bone back into its allowed range.
+// `angles` is where to start from, usually the current pose, so the chain doesn't jump around.
+#[allow(clippy::too_many_arguments)]
+pub fn fabrik(
+    base: Vec2,
+    reference: Vec2,
+    lengths: &[f32],
+    angles: &[f32],
+    limits: &[JointLimit],
+    target: Vec2,
+    tolerance: f32,
+    max_iterations: usize,
+) -> IkSolution {
+    let mut positions = forward_kinematics(base, reference, lengths, angles);
+    let mut solution = IkSolution {
+        angles: joint_angles(&positions, reference),
+        distance: positions.last().unwrap().distance(target),
+        iterations: 0,
+    };
+
+    while solution.distance > tolerance && solution.iterations < max_iterations {
+        solution.iterations += 1;
+
+        // Backward, from the tip to the base
+        *positions.last_mut().unwrap() = target;
+        for i in (0..lengths.len()).rev() {
+            let direction = (positions[i] - positions[i + 1]).normalize_or_zero();
+            positions[i] = positions[i + 1] + direction * lengths[i];
+        }
+
+        // Forward, from the base to the tip, keeping every joint in its limits
+        positions[0] = base;
+        let mut previous = reference.normalize_or_zero();
+        for i in 0..lengths.len() {
+            let mut direction = (positions[i + 1] - positions[i]).normalize_or_zero();
+            if direction == Vec2::ZERO {
+                direction = previous;
+            }
+
+            let limit = limits.get(i).copied().unwrap_or(JointLimit::FREE);
+            let angle = limit.clamp(signed_angle(previous, direction));
+            direction = Vec2::from_angle(angle).rotate(previous);
+
+            positions[i + 1] = positions[i] + direction * lengths[i];
+            solution.angles[i] = angle;
+            previous = direction;
+        }
+
+        let distance = positions.last().unwrap().distance(target);
+        // Stuck, most likely because the target is out of reach
+        let stuck = (solution.distance - distance).abs() < tolerance * 1e-3;
+        solution.distance = distance;
+        if stuck {
+            break;
+        }
+    }
+
+    solution
+}
+
+// Counter clockwise angle from one direction to another. Vec2::angle_between uses acos,
+// which loses almost all precision for small angles, atan2 doesn't.
+fn signed_angle(from: Vec2, to: Vec2) -> f32 {
+    from.perp_dot(to).atan2(from.dot(to))
+}
+
+// Into -PI..=PI
+fn wrap_angle(angle: f32) -> f32 {
+    let wrapped = (angle + PI).rem_euclid(2.0 * PI) - PI;
+    if wrapped == -PI {
+        PI
+    } else {
+        wrapped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Lcg;
+
+    const EPSILON: f32 = 1e-3;
+
+    fn tip(base: Vec2, reference: Vec2, lengths: &[f32], angles: &[f32]) -> Vec2 {
+        *forward_kinematics(base, reference, lengths, angles)
+            .last()
+            .unwrap()
+    }
+
+    #[test]
+    fn forward_kinematics_and_back() {
+        let lengths = [80.0, 60.0, 40.0];
+        let angles = [0.5, -1.0, 2.0];
+        let positions = forward_kinematics(Vec2::new(10.0, 20.0), Vec2::Y, &lengths, &angles);
+        assert_eq!(positions.len(), 4);
+        // The first bone turned half a radian counter clockwise from up
+        let first = Vec2::from_angle(0.5).rotate(Vec2::Y) * 80.0;
+        assert!(positions[1].abs_diff_eq(Vec2::new(10.0, 20.0) + first, EPSILON));
+
+        let back = joint_angles(&positions, Vec2::Y);
+        for (angle, expected) in back.iter().zip(angles) {
+            assert!((angle - expected).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn two_bone_reaches_targets() {
+        let mut random = Lcg(1);
+        let base = Vec2::new(-300.0, -200.0);
+        let lengths = [80.0, 50.0];
+        for _ in 0..1000 {
+            // Anywhere between the shortest and longest reach
+            let distance = random.next_range(30.0 + 0.1, 130.0);
+            let direction = Vec2::from_angle(random.next_range(-PI, PI));
+            let target = base + direction * distance;
+
+            for bend in [BendDirection::Clockwise, BendDirection::CounterClockwise] {
+                let angles =
+                    two_bone_ik(base, Vec2::Y, lengths, target, bend, [JointLimit::FREE; 2]);
+                let reached = tip(base, Vec2::Y, &lengths, &angles);
+                assert!(
+                    reached.distance(target) < 0.01,
+                    "{} is not {}",
+                    reached,
+                    target
+                );
+
+                // The middle joint bends the way it was asked to
+                match bend {
+                    BendDirection::Clockwise => assert!(angles[1] <= 0.0),
+                    BendDirection::CounterClockwise => assert!(angles[1] >= 0.0),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn two_bone_out_of_reach() {
+        let lengths = [80.0, 50.0];
+        let target = Vec2::new(500.0, 0.0);
+        let angles = two_bone_ik(
+            Vec2::ZERO,
+            Vec2::Y,
+            lengths,
+            target,
+            BendDirection::Clockwise,
+            [JointLimit::FREE; 2],
+        );
+        // Stretched straight towards the target
+        assert!(
+            tip(Vec2::ZERO, Vec2::Y, &lengths, &angles).abs_diff_eq(Vec2::new(130.0, 0.0), EPSILON)
+        );
+
+        // Too close, folded up as far as it goes
+        let angles = two_bone_ik(
+            Vec2::ZERO,
+            Vec2::Y,
+            lengths,
+            Vec2::ZERO,
+            BendDirection::Clockwise,
+            [JointLimit::FREE; 2],
+        );
+        assert!((tip(Vec2::ZERO, Vec2::Y, &lengths, &angles).length() - 30.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn two_bone_limits() {
+        let limits = [
+            JointLimit::FREE,
+            JointLimit::symmetric(f32::to_radians(45.0)),
+        ];
+        let angles = two_bone_ik(
+            Vec2::ZERO,
+            Vec2::Y,
+            [80.0, 50.0],
+            Vec2::new(0.0, 40.0),
+            BendDirection::CounterClockwise,
+            limits,
+        );
+        assert!((angles[1] - f32::to_radians(45.0)).abs() < EPSILON);
+        // Too close to reach, but still pointing straight at it
+        let reached = tip(Vec2::ZERO, Vec2::Y, &[80.0, 50.0], &angles);
+        assert!(reached.normalize().abs_diff_eq(Vec2::Y, EPSILON));
+    }
+
+    #[test]
+    fn two_bone_reaches_within_limits() {
+        let mut random = Lcg(4);
+        let base = Vec2::new(-300.0, -200.0);
+        let lengths = [80.0, 50.0];
+        // An elbow that bends a long way clockwise but hardly at all the other way
+        let elbow = JointLimit {
+            min: -2.5,
+            max: 0.2,
+        };
+        for _ in 0..1000 {
+            // Targets made from poses inside the limits, so they can always be reached
+            let pose = [
+                random.next_range(-PI, PI),
+                random.next_range(elbow.min, elbow.max),
+            ];
+            let target = tip(base, Vec2::Y, &lengths, &pose);
+
+            for bend in [BendDirection::Clockwise, BendDirection::CounterClockwise] {
+                let angles = two_bone_ik(
+                    base,
+                    Vec2::Y,
+                    lengths,
+                    target,
+                    bend,
+                    [JointLimit::FREE, elbow],
+                );
+                assert!(angles[1] >= elbow.min && angles[1] <= elbow.max);
+                let reached = tip(base, Vec2::Y, &lengths, &angles);
+                assert!(
+                    reached.distance(target) < 0.01,
+                    "{} is not {}",
+                    reached,
+                    target
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fabrik_converges() {
+        let mut random = Lcg(2);
+        let base = Vec2::new(-300.0, -200.0);
+        let lengths = [80.0, 70.0, 60.0, 50.0, 40.0];
+        let limits = [JointLimit::FREE; 5];
+
+        let mut total_iterations = 0;
+        for _ in 0..500 {
+            // Random targets that can be reached, made from a random pose
+            let pose: Vec<f32> = (0..5).map(|_| random.next_range(-PI, PI)).collect();
+            let target = tip(base, Vec2::Y, &lengths, &pose);
+
+            let solution = fabrik(
+                base,
+                Vec2::Y,
+                &lengths,
+                &[0.0; 5],
+                &limits,
+                target,
+                0.01,
+                200,
+            );
+            assert!(
+                solution.distance < 0.01,
+                "stopped {} away from {}",
+                solution.distance,
+                target
+            );
+            // The angles it gives back really put the tip there
+            let reached = tip(base, Vec2::Y, &lengths, &solution.angles);
+            assert!(reached.distance(target) < 0.01 + EPSILON);
+            total_iterations += solution.iterations;
+        }
+        // Most targets take a handful of iterations, a few awkward ones take a lot more
+        assert!(total_iterations < 500 * 10);
+    }
+
+    #[test]
+    fn fabrik_out_of_reach() {
+        let lengths = [80.0, 70.0, 60.0];
+        let target = Vec2::new(0.0, -1000.0);
+        let solution = fabrik(
+            Vec2::ZERO,
+            Vec2::Y,
+            &lengths,
+            &[0.3, 0.2, 0.1],
+            &[JointLimit::FREE; 3],
+            target,
+            0.01,
+            100,
+        );
+        // Pointing straight at the target, as close as it gets
+        assert!((solution.distance - (1000.0 - 210.0)).abs() < 0.1);
+        assert!(solution.iterations < 100);
+    }
+
+    #[test]
+    fn fabrik_keeps_joint_limits() {
+        let mut random = Lcg(3);
+        let base = Vec2::ZERO;
+        let lengths = [80.0, 70.0, 60.0, 50.0];
+        let bend = JointLimit::symmetric(f32::to_radians(60.0));
+        let limits = [JointLimit::FREE, bend, bend, bend];
+
+        let mut reached = 0;
+        for _ in 0..500 {
+            let pose: Vec<f32> = limits
+                .iter()
+                .map(|limit| random.next_range(limit.min, limit.max))
+                .collect();
+            let target = tip(base, Vec2::Y, &lengths, &pose);
+
+            let solution = fabrik(
+                base,
+                Vec2::Y,
+                &lengths,
+                &[0.0; 4],
+                &limits,
+                target,
+                0.01,
+                200,
+            );
+            for (angle, limit) in solution.angles.iter().zip(limits) {
+                assert!(*angle >= limit.min - EPSILON && *angle <= limit.max + EPSILON);
+            }
+            if solution.distance < 0.01 {
+                reached += 1;
+            }
+        }
+        // Clamping can leave FABRIK stuck short of some targets, but it should reach nearly all of them
+        assert!(reached >= 450, "only reached {} of 500", reached);
+    }
+}
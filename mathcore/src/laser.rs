@@ -0,0 +1,436 @@
+use bevy::prelude::*;
+
+// A laser that keeps bouncing off whatever it hits, or bending through it when it is transparent.
+// The tracing doesn't know about physics, it asks a `cast` function for the first thing
+// along each ray, so it works with rapier or with plain maths in tests.
+
+// Start the next ray this far off the surface, so it doesn't hit the surface it just left
+pub const SURFACE_OFFSET: f32 = 0.01;
+
+// The refractive index of empty space around everything
+pub const AIR_REFRACTIVE_INDEX: f32 = 1.0;
+
+// Colliders with this let the laser through, bending it by Snell's law.
+// Light of different colors bends by slightly different amounts, `dispersion` is how much
+// the index goes up for blue light and down for red light, which is how a prism splits a beam.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct RefractiveIndex {
+    pub index: f32,
+    pub dispersion: f32,
+}
+
+// What a ray ran into, `toi` is how far along the ray direction the hit is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub entity: Entity,
+    pub toi: f32,
+    // Pointing out of the shape or back at the ray, either works
+    pub normal: Vec2,
+    // None for shapes the laser can't go through
+    pub refractive_index: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HitKind {
+    Reflected,
+    Refracted,
+    // Inside a transparent shape and too flat to get out, so it reflected instead
+    TotalInternalReflection,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LaserHit {
+    pub entity: Entity,
+    pub point: Vec2,
+    // The normal `cast` gave for the surface that was hit
+    pub normal: Vec2,
+    pub kind: HitKind,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LaserPath {
+    // The start, every hit point and where the laser ended, in order
+    pub points: Vec<Vec2>,
+    pub hits: Vec<LaserHit>,
+}
+
+impl LaserPath {
+    pub fn length(&self) -> f32 {
+        self.points
+            .windows(2)
+            .map(|segment| segment[0].distance(segment[1]))
+            .sum()
+    }
+}
+
+// Mirror `direction` in a surface with the given unit normal.
+// The part of the direction going into the surface is (n·v) n, taking it away once
+// would slide along the surface and taking it away twice bounces off it.
+pub fn reflect(direction: Vec2, normal: Vec2) -> Vec2 {
+    direction - 2.0 * direction.dot(normal) * normal
+}
+
+// Bend a unit `direction` going through a surface by Snell's law, n1 sin(θ1) = n2 sin(θ2).
+// `normal` is the unit normal on the side the direction comes from and `eta` is n1 / n2.
+// The part along the surface gets scaled by eta, which is what keeps n sin(θ) the same,
+// and the part along the normal is whatever makes the result unit length again.
+// None when sin(θ2) would have to be more than 1, past the critical angle all the light reflects.
+pub fn refract(direction: Vec2, normal: Vec2, eta: f32) -> Option<Vec2> {
+    let cos_incoming = -normal.dot(direction);
+    let sin_squared_outgoing = eta * eta * (1.0 - cos_incoming * cos_incoming);
+    if sin_squared_outgoing > 1.0 {
+        return None;
+    }
+
+    let cos_outgoing = (1.0 - sin_squared_outgoing).sqrt();
+    Some(eta * direction + (eta * cos_incoming - cos_outgoing) * normal)
+}
+
+// Follow a laser from `origin` along `direction`, hitting things at most `max_bounces` + 1 times
+// and stopping once it has gone `max_length` in total.
+// `cast(origin, unit direction, max distance)` gives the first hit along a ray, if there is one.
+// Rays starting inside a transparent shape have to hit its boundary on the way out.
+pub fn trace_laser(
+    origin: Vec2,
+    direction: Vec2,
+    max_bounces: usize,
+    max_length: f32,
+    mut cast: impl FnMut(Vec2, Vec2, f32) -> Option<RayHit>,
+) -> LaserPath {
+    let mut path = LaserPath {
+        points: vec![origin],
+        hits: Vec::new(),
+    };
+
+    let mut position = origin;
+    let mut direction = direction.normalize_or_zero();
+    let mut length_left = max_length;
+    if direction == Vec2::ZERO {
+        return path;
+    }
+
+    // The transparent shapes the laser is inside of, the last one is the one it is in right now
+    let mut media: Vec<(Entity, f32)> = Vec::new();
+
+    loop {
+        let hit = match cast(position, direction, length_left) {
+            Some(hit) if hit.toi <= length_left => hit,
+            _ => {
+                path.points.push(position + direction * length_left);
+                return path;
+            }
+        };
+
+        let point = position + direction * hit.toi;
+        length_left -= hit.toi;
+
+        // The normal on the side the laser comes from. Going by which shapes the laser is in
+        // instead of which way the normal points, since casts from inside a shape
+        // don't all agree on which way the normal should point.
+        let facing_normal = if direction.dot(hit.normal) < 0.0 {
+            hit.normal
+        } else {
+            -hit.normal
+        };
+        let entering = !media.iter().any(|(entity, _)| *entity == hit.entity);
+
+        let refracted = hit.refractive_index.and_then(|index| {
+            let current_index = media
+                .last()
+                .map_or(AIR_REFRACTIVE_INDEX, |(_, index)| *index);
+            let (from_index, to_index) = if entering {
+                (current_index, index)
+            } else {
+                // Back out into whatever is around this shape
+                let outside_index = media
+                    .iter()
+                    .rev()
+                    .find(|(entity, _)| *entity != hit.entity)
+                    .map_or(AIR_REFRACTIVE_INDEX, |(_, index)| *index);
+                (index, outside_index)
+            };
+            refract(direction, facing_normal, from_index / to_index).map(|bent| (bent, index))
+        });
+
+        let kind = match (refracted, hit.refractive_index) {
+            (Some((bent, index)), _) => {
+                if entering {
+                    media.push((hit.entity, index));
+                } else {
+                    media.retain(|(entity, _)| *entity != hit.entity);
+                }
+                direction = bent.normalize_or_zero();
+                position = point - facing_normal * SURFACE_OFFSET;
+                HitKind::Refracted
+            }
+            (None, refractive_index) => {
+                direction = reflect(direction, facing_normal).normalize_or_zero();
+                position = point + facing_normal * SURFACE_OFFSET;
+                if refractive_index.is_some() {
+                    HitKind::TotalInternalReflection
+                } else {
+                    HitKind::Reflected
+                }
+            }
+        };
+
+        path.points.push(point);
+        path.hits.push(LaserHit {
+            entity: hit.entity,
+            point,
+            normal: hit.normal,
+            kind,
+        });
+
+        if path.hits.len() > max_bounces || length_left <= 0.0 {
+            return path;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-3;
+
+    // The inside of a box from -half to half, like the walls of assignment4
+    fn cast_inside_box(half: Vec2) -> impl FnMut(Vec2, Vec2, f32) -> Option<RayHit> {
+        move |origin, direction, max_toi| {
+            let mut closest: Option<RayHit> = None;
+            for (axis, normal) in [
+                (0, Vec2::NEG_X),
+                (0, Vec2::X),
+                (1, Vec2::NEG_Y),
+                (1, Vec2::Y),
+            ] {
+                // The wall faces into the box, against its normal's sign
+                let wall = -normal[axis] * half[axis];
+                if direction[axis] * normal[axis] >= 0.0 {
+                    continue;
+                }
+                let toi = (wall - origin[axis]) / direction[axis];
+                let closer = match closest {
+                    Some(hit) => toi < hit.toi,
+                    None => true,
+                };
+                if toi >= 0.0 && toi <= max_toi && closer {
+                    closest = Some(RayHit {
+                        entity: Entity::from_raw(axis as u32),
+                        toi,
+                        normal,
+                        refractive_index: None,
+                    });
+                }
+            }
+            closest
+        }
+    }
+
+    #[test]
+    fn reflection() {
+        assert_eq!(reflect(Vec2::new(1.0, -1.0), Vec2::Y), Vec2::new(1.0, 1.0));
+        assert_eq!(reflect(Vec2::new(-3.0, 0.0), Vec2::X), Vec2::new(3.0, 0.0));
+        // Sliding along the surface is left alone
+        assert_eq!(reflect(Vec2::X, Vec2::Y), Vec2::X);
+    }
+
+    #[test]
+    fn bounces_around_a_box() {
+        let half = Vec2::new(100.0, 100.0);
+        let path = trace_laser(
+            Vec2::ZERO,
+            Vec2::new(1.0, 0.5),
+            3,
+            10_000.0,
+            cast_inside_box(half),
+        );
+
+        // Bounces off the right wall, the ceiling, the left wall and the right wall again,
+        // stopping after the last one because it has no bounces left
+        assert_eq!(path.hits.len(), 4);
+        assert_eq!(path.points.len(), 5);
+        for hit in path.hits.iter() {
+            assert!(hit.point.x.abs() > half.x - EPSILON || hit.point.y.abs() > half.y - EPSILON);
+        }
+
+        // The angle going in is the angle going out
+        for (i, hit) in path.hits.iter().enumerate().take(path.hits.len() - 1) {
+            let incoming = (hit.point - path.points[i]).normalize();
+            let outgoing = (path.points[i + 2] - hit.point).normalize();
+            assert!((incoming.dot(hit.normal) + outgoing.dot(hit.normal)).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn stops_at_max_length() {
+        let half = Vec2::new(100.0, 50.0);
+        let path = trace_laser(Vec2::ZERO, Vec2::X, 10, 350.0, cast_inside_box(half));
+
+        // Right wall at 100, back to the left wall at -100, then 50 more
+        assert_eq!(path.hits.len(), 2);
+        assert!((path.length() - 350.0).abs() < 0.1);
+        assert!(path
+            .points
+            .last()
+            .unwrap()
+            .abs_diff_eq(Vec2::new(-50.0, 0.0), 0.1));
+    }
+
+    #[test]
+    fn nothing_to_hit() {
+        let path = trace_laser(Vec2::ONE, Vec2::Y, 5, 300.0, |_, _, _| None);
+        assert!(path.hits.is_empty());
+        assert_eq!(path.points, vec![Vec2::ONE, Vec2::new(1.0, 301.0)]);
+    }
+
+    // Convex polygons going counter clockwise, some of them transparent.
+    // Works from the inside too, which the box above doesn't.
+    fn cast_polygons(
+        polygons: Vec<(Vec<Vec2>, Option<f32>)>,
+    ) -> impl FnMut(Vec2, Vec2, f32) -> Option<RayHit> {
+        move |origin, direction, max_toi| {
+            let mut closest: Option<RayHit> = None;
+            for (i, (points, refractive_index)) in polygons.iter().enumerate() {
+                for (j, start) in points.iter().enumerate() {
+                    let edge = points[(j + 1) % points.len()] - *start;
+                    let denominator = direction.perp_dot(edge);
+                    if denominator.abs() < 1e-9 {
+                        continue;
+                    }
+                    let toi = (*start - origin).perp_dot(edge) / denominator;
+                    let along = (*start - origin).perp_dot(direction) / denominator;
+                    let closer = match closest {
+                        Some(hit) => toi < hit.toi,
+                        None => true,
+                    };
+                    if toi >= 0.0 && toi <= max_toi && (0.0..=1.0).contains(&along) && closer {
+                        closest = Some(RayHit {
+                            entity: Entity::from_raw(i as u32),
+                            toi,
+                            // Outwards for a counter clockwise polygon
+                            normal: Vec2::new(edge.y, -edge.x).normalize(),
+                            refractive_index: *refractive_index,
+                        });
+                    }
+                }
+            }
+            closest
+        }
+    }
+
+    fn rectangle(min: Vec2, max: Vec2) -> Vec<Vec2> {
+        vec![min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)]
+    }
+
+    // Angle from the normal, for checking Snell's law
+    fn sin_from_normal(direction: Vec2, normal: Vec2) -> f32 {
+        direction.normalize().perp_dot(normal).abs()
+    }
+
+    #[test]
+    fn snells_law() {
+        let normal = Vec2::Y;
+        for degrees in (0..90).step_by(5) {
+            let angle = f32::to_radians(degrees as f32);
+            // Coming down onto a surface facing up
+            let direction = Vec2::new(angle.sin(), -angle.cos());
+
+            for (from_index, to_index) in [(1.0, 1.5), (1.0, 1.33), (1.33, 1.5), (1.0, 1.0)] {
+                let bent = refract(direction, normal, from_index / to_index).unwrap();
+                assert!((bent.length() - 1.0).abs() < EPSILON);
+                // Still going down and to the same side
+                assert!(bent.y < 0.0 && bent.x * direction.x >= 0.0);
+                assert!(
+                    (from_index * sin_from_normal(direction, normal)
+                        - to_index * sin_from_normal(bent, normal))
+                    .abs()
+                        < EPSILON
+                );
+
+                // And the same path backwards
+                let back = refract(-bent, -normal, to_index / from_index).unwrap();
+                assert!(back.abs_diff_eq(-direction, EPSILON));
+            }
+        }
+
+        // Straight on doesn't bend
+        assert!(refract(Vec2::NEG_Y, Vec2::Y, 1.0 / 1.5)
+            .unwrap()
+            .abs_diff_eq(Vec2::NEG_Y, EPSILON));
+    }
+
+    #[test]
+    fn total_internal_reflection() {
+        // Glass to air has a critical angle of asin(1 / 1.5), about 41.8 degrees
+        let critical = (1.0f32 / 1.5).asin();
+        let normal = Vec2::Y;
+        let eta = 1.5;
+
+        let below = critical - 0.01;
+        assert!(refract(Vec2::new(below.sin(), -below.cos()), normal, eta).is_some());
+        let above = critical + 0.01;
+        assert_eq!(
+            refract(Vec2::new(above.sin(), -above.cos()), normal, eta),
+            None
+        );
+    }
+
+    #[test]
+    fn through_a_glass_slab() {
+        // A slab of glass between x = 100 and 200, a wall far behind it
+        let slab = rectangle(Vec2::new(100.0, -1000.0), Vec2::new(200.0, 1000.0));
+        let wall = rectangle(Vec2::new(400.0, -1000.0), Vec2::new(500.0, 1000.0));
+        let direction = Vec2::new(1.0, 0.5).normalize();
+        let path = trace_laser(
+            Vec2::ZERO,
+            direction,
+            5,
+            10_000.0,
+            cast_polygons(vec![(slab, Some(1.5)), (wall, None)]),
+        );
+
+        let kinds: Vec<HitKind> = path.hits.iter().map(|hit| hit.kind).collect();
+        assert_eq!(
+            kinds[..3],
+            [HitKind::Refracted, HitKind::Refracted, HitKind::Reflected]
+        );
+
+        // Bent towards the normal inside, then back to the same direction on the way out
+        let inside = (path.points[2] - path.points[1]).normalize();
+        assert!(inside.y < direction.y);
+        let after = (path.points[3] - path.points[2]).normalize();
+        assert!(after.abs_diff_eq(direction, EPSILON));
+    }
+
+    #[test]
+    fn prism_total_internal_reflection() {
+        // A right angled prism, light coming straight into the short side
+        // hits the long side at 45 degrees, past glass's critical angle, and turns by 90 degrees
+        let prism = vec![
+            Vec2::new(100.0, -50.0),
+            Vec2::new(200.0, -50.0),
+            Vec2::new(100.0, 50.0),
+        ];
+        let path = trace_laser(
+            Vec2::new(0.0, 0.0),
+            Vec2::X,
+            3,
+            1000.0,
+            cast_polygons(vec![(prism, Some(1.5))]),
+        );
+
+        let kinds: Vec<HitKind> = path.hits.iter().map(|hit| hit.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                HitKind::Refracted,
+                HitKind::TotalInternalReflection,
+                HitKind::Refracted
+            ]
+        );
+        // Straight down out of the bottom
+        let out = (path.points[4] - path.points[3]).normalize();
+        assert!(out.abs_diff_eq(Vec2::NEG_Y, EPSILON));
+    }
+}
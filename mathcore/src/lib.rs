@@ -0,0 +1,20 @@
+// Code shared between the assignments so fixes only have to be made once.
+
+pub mod containment;
+pub mod cursor;
+pub mod debug_draw;
+pub mod font;
+pub mod hud;
+pub mod ik;
+pub mod laser;
+pub mod matrix;
+pub mod mesh;
+pub mod player;
+pub mod polygon;
+pub mod ray;
+pub mod sdf;
+pub mod trigger;
+pub mod visibility;
+
+#[cfg(test)]
+mod test_support;
@@ -0,0 +1,337 @@
+use bevy::prelude::*;
+use std::ops::Mul;
+
+// A 2D affine transform written out as a 3x3 matrix, so the maths behind Transform can be seen.
+// A point (x, y) is treated as the column (x, y, 1) and a direction as (x, y, 0),
+// which is why translation moves points but not directions.
+//
+//  | a  b  tx |
+//  | c  d  ty |
+//  | 0  0  1  |
+//
+// The bottom row is always 0 0 1 for an affine transform, it is kept so the maths reads like the book.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix3 {
+    // rows[row][column]
+    pub rows: [[f32; 3]; 3],
+}
+
+impl Default for Matrix3 {
+    fn default() -> Self {
+        Matrix3::IDENTITY
+    }
+}
+
+impl Matrix3 {
+    pub const IDENTITY: Matrix3 = Matrix3 {
+        rows: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+    };
+
+    pub fn from_translation(translation: Vec2) -> Self {
+        Matrix3 {
+            rows: [
+                [1.0, 0.0, translation.x],
+                [0.0, 1.0, translation.y],
+                [0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    // Counter clockwise by `angle` radians. The columns are where the x and y axes end up.
+    pub fn from_angle(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Matrix3 {
+            rows: [[cos, -sin, 0.0], [sin, cos, 0.0], [0.0, 0.0, 1.0]],
+        }
+    }
+
+    pub fn from_scale(scale: Vec2) -> Self {
+        Matrix3 {
+            rows: [[scale.x, 0.0, 0.0], [0.0, scale.y, 0.0], [0.0, 0.0, 1.0]],
+        }
+    }
+
+    // Scale first, then rotate, then translate, the same order Transform uses.
+    pub fn from_scale_angle_translation(scale: Vec2, angle: f32, translation: Vec2) -> Self {
+        Matrix3::from_translation(translation)
+            * Matrix3::from_angle(angle)
+            * Matrix3::from_scale(scale)
+    }
+
+    // Only the rotation around z, the scale along x and y and the translation along x and y are kept.
+    pub fn from_transform(transform: &Transform) -> Self {
+        let x_axis = transform.rotation * Vec3::X;
+        let angle = x_axis.y.atan2(x_axis.x);
+        Matrix3::from_scale_angle_translation(
+            transform.scale.truncate(),
+            angle,
+            transform.translation.truncate(),
+        )
+    }
+
+    // `z` is the depth to put the transform at, the matrix has no z of its own.
+    pub fn to_transform(&self, z: f32) -> Transform {
+        let (scale, angle, translation) = self.to_scale_angle_translation();
+        Transform {
+            translation: translation.extend(z),
+            rotation: Quat::from_rotation_z(angle),
+            scale: scale.extend(1.0),
+        }
+    }
+
+    // Where the local x and y axes end up, the first two columns.
+    pub fn x_axis(&self) -> Vec2 {
+        Vec2::new(self.rows[0][0], self.rows[1][0])
+    }
+
+    pub fn y_axis(&self) -> Vec2 {
+        Vec2::new(self.rows[0][1], self.rows[1][1])
+    }
+
+    pub fn translation(&self) -> Vec2 {
+        Vec2::new(self.rows[0][2], self.rows[1][2])
+    }
+
+    // How much the transform scales areas, negative when it mirrors.
+    // Expanded along the bottom row, which for an affine transform leaves only the 2x2 part.
+    pub fn determinant(&self) -> f32 {
+        let m = &self.rows;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    // The transform that undoes this one, None when something was scaled to nothing
+    // or the axes are so close to parallel that the inverse would be mostly rounding error.
+    // The inverse of the 2x2 part is the swapped and negated 2x2 over the determinant,
+    // and the translation is undone by moving back through that inverse.
+    pub fn inverse(&self) -> Option<Self> {
+        let determinant = self.determinant();
+        // The determinant is the area between the axes, so compare it to their lengths
+        // to get how parallel they are no matter how big or small the scale is
+        if determinant.abs() <= f32::EPSILON * self.x_axis().length() * self.y_axis().length() {
+            return None;
+        }
+
+        let [[a, b, tx], [c, d, ty], _] = self.rows;
+        let inverse_a = d / determinant;
+        let inverse_b = -b / determinant;
+        let inverse_c = -c / determinant;
+        let inverse_d = a / determinant;
+
+        Some(Matrix3 {
+            rows: [
+                [inverse_a, inverse_b, -(inverse_a * tx + inverse_b * ty)],
+                [inverse_c, inverse_d, -(inverse_c * tx + inverse_d * ty)],
+                [0.0, 0.0, 1.0],
+            ],
+        })
+    }
+
+    // The opposite of `from_scale_angle_translation`. A mirrored matrix has a negative x scale,
+    // since a mirror can't be told apart from a 180 degree turn with the other axis mirrored.
+    pub fn to_scale_angle_translation(&self) -> (Vec2, f32, Vec2) {
+        let sign = self.determinant().signum();
+        let x_axis = self.x_axis();
+        let scale = Vec2::new(x_axis.length() * sign, self.y_axis().length());
+        let angle = (x_axis.y * sign).atan2(x_axis.x * sign);
+        (scale, angle, self.translation())
+    }
+
+    // Points are (x, y, 1), so they get moved by the translation.
+    pub fn transform_point(&self, point: Vec2) -> Vec2 {
+        let m = &self.rows;
+        Vec2::new(
+            m[0][0] * point.x + m[0][1] * point.y + m[0][2],
+            m[1][0] * point.x + m[1][1] * point.y + m[1][2],
+        )
+    }
+
+    // Directions are (x, y, 0), so they only get rotated and scaled.
+    pub fn transform_vector(&self, vector: Vec2) -> Vec2 {
+        let m = &self.rows;
+        Vec2::new(
+            m[0][0] * vector.x + m[0][1] * vector.y,
+            m[1][0] * vector.x + m[1][1] * vector.y,
+        )
+    }
+}
+
+// `parent * child` applies the child first and then the parent,
+// which turns a position local to the child into one local to the parent's parent.
+impl Mul for Matrix3 {
+    type Output = Matrix3;
+
+    fn mul(self, other: Matrix3) -> Matrix3 {
+        let mut rows = [[0.0; 3]; 3];
+        for (row, output_row) in rows.iter_mut().enumerate() {
+            for (column, value) in output_row.iter_mut().enumerate() {
+                *value = (0..3)
+                    .map(|i| self.rows[row][i] * other.rows[i][column])
+                    .sum();
+            }
+        }
+        Matrix3 { rows }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Lcg;
+    use bevy::math::Affine2;
+
+    const EPSILON: f32 = 1e-3;
+
+    // Scales between 0.25 and 4 either way, mirrored half of the time
+    fn random_scale(random: &mut Lcg) -> Vec2 {
+        let mut scale = || {
+            let size = random.next_range(0.25, 4.0);
+            if random.next_f32() < 0.5 {
+                -size
+            } else {
+                size
+            }
+        };
+        Vec2::new(scale(), scale())
+    }
+
+    // The same random transform as both a Matrix3 and a glam Affine2
+    fn random_pairs() -> Vec<(Matrix3, Affine2)> {
+        let mut random = Lcg(11);
+        (0..1000)
+            .map(|_| {
+                let scale = random_scale(&mut random);
+                let angle = random.next_range(-std::f32::consts::PI, std::f32::consts::PI);
+                let translation = random.next_point(-500.0, 500.0);
+                (
+                    Matrix3::from_scale_angle_translation(scale, angle, translation),
+                    Affine2::from_scale_angle_translation(scale, angle, translation),
+                )
+            })
+            .collect()
+    }
+
+    fn assert_same(matrix: &Matrix3, affine: &Affine2) {
+        let close = matrix.x_axis().abs_diff_eq(affine.x_axis, EPSILON)
+            && matrix.y_axis().abs_diff_eq(affine.y_axis, EPSILON)
+            && matrix
+                .translation()
+                .abs_diff_eq(affine.translation, EPSILON);
+        assert!(close, "{:?} is not {:?}", matrix, affine);
+        assert_eq!(matrix.rows[2], [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn construction() {
+        for (matrix, affine) in random_pairs() {
+            assert_same(&matrix, &affine);
+        }
+        assert_same(&Matrix3::IDENTITY, &Affine2::IDENTITY);
+    }
+
+    #[test]
+    fn composition() {
+        let pairs = random_pairs();
+        for window in pairs.windows(2) {
+            let [(matrix_a, affine_a), (matrix_b, affine_b)] = [window[0], window[1]];
+            assert_same(&(matrix_a * matrix_b), &(affine_a * affine_b));
+        }
+    }
+
+    #[test]
+    fn determinant() {
+        for (matrix, affine) in random_pairs() {
+            let expected = affine.matrix2.determinant();
+            assert!((matrix.determinant() - expected).abs() < EPSILON * expected.abs().max(1.0));
+        }
+    }
+
+    #[test]
+    fn inverse() {
+        for (matrix, affine) in random_pairs() {
+            let inverse = matrix.inverse().unwrap();
+            assert_same(&inverse, &affine.inverse());
+            assert_same(&(matrix * inverse), &Affine2::IDENTITY);
+        }
+
+        assert_eq!(Matrix3::from_scale(Vec2::new(0.0, 1.0)).inverse(), None);
+    }
+
+    #[test]
+    fn inverse_of_tiny_and_huge_scales() {
+        // Scaled down to a ten thousandth the determinant is only 1e-8, but it still undoes
+        let tiny =
+            Matrix3::from_scale_angle_translation(Vec2::splat(1e-4), 0.3, Vec2::new(5e-4, -2e-4));
+        let inverse = tiny.inverse().unwrap();
+        assert_same(&(tiny * inverse), &Affine2::IDENTITY);
+        let point = Vec2::new(30.0, 40.0);
+        assert!(inverse
+            .transform_point(tiny.transform_point(point))
+            .abs_diff_eq(point, EPSILON));
+
+        // Big axes that are almost parallel have a determinant far from 0 and still can't be undone
+        let flat = Matrix3 {
+            rows: [[1000.0, 1000.0, 0.0], [0.0, 1e-4, 0.0], [0.0, 0.0, 1.0]],
+        };
+        assert!(flat.determinant() > 0.05);
+        assert_eq!(flat.inverse(), None);
+    }
+
+    // glam has no decomposition for Affine2 yet, so check the parts against its axes
+    // and that building the matrix back up from them gives the same transform
+    #[test]
+    fn decomposition() {
+        for (matrix, affine) in random_pairs() {
+            let (scale, angle, translation) = matrix.to_scale_angle_translation();
+            assert!((scale.x.abs() - affine.x_axis.length()).abs() < EPSILON);
+            assert!((scale.y.abs() - affine.y_axis.length()).abs() < EPSILON);
+            assert!(translation.abs_diff_eq(affine.translation, EPSILON));
+            assert!((-std::f32::consts::PI..=std::f32::consts::PI).contains(&angle));
+
+            assert_same(
+                &Matrix3::from_scale_angle_translation(scale, angle, translation),
+                &affine,
+            );
+        }
+    }
+
+    #[test]
+    fn points_and_vectors() {
+        let mut random = Lcg(3);
+        for (matrix, affine) in random_pairs() {
+            let point = random.next_point(-200.0, 200.0);
+            assert!(matrix
+                .transform_point(point)
+                .abs_diff_eq(affine.transform_point2(point), EPSILON));
+            assert!(matrix
+                .transform_vector(point)
+                .abs_diff_eq(affine.transform_vector2(point), EPSILON));
+        }
+
+        // Only points get moved
+        let translation = Matrix3::from_translation(Vec2::new(10.0, 20.0));
+        assert_eq!(
+            translation.transform_point(Vec2::ONE),
+            Vec2::new(11.0, 21.0)
+        );
+        assert_eq!(translation.transform_vector(Vec2::ONE), Vec2::ONE);
+    }
+
+    #[test]
+    fn transform_round_trip() {
+        let mut random = Lcg(5);
+        for (matrix, affine) in random_pairs() {
+            let transform = matrix.to_transform(3.0);
+            assert_eq!(transform.translation.z, 3.0);
+            assert_same(&Matrix3::from_transform(&transform), &affine);
+
+            // Transform agrees about where points go
+            let point = random.next_point(-200.0, 200.0);
+            assert!(transform
+                .transform_point(point.extend(0.0))
+                .truncate()
+                .abs_diff_eq(matrix.transform_point(point), EPSILON));
+        }
+    }
+}
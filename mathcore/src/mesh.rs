@@ -0,0 +1,365 @@
+use bevy::prelude::*;
+use bevy::render::mesh::Indices;
+use bevy::render::render_resource::PrimitiveTopology;
+
+// How the outside of a corner is filled in where two edges of a stroke meet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineJoin {
+    // Carry both edges on until they meet in a point. A sharp corner makes a long point, so when
+    // it would reach further than `limit` half widths from the corner it gets beveled instead,
+    // the same as SVG's stroke-miterlimit.
+    Miter { limit: f32 },
+    // Cut the corner off straight
+    Bevel,
+}
+
+// Vertex and index buffers for a flat triangle mesh. Shapes get pushed into it one after another
+// so many of them end up in a single mesh, and the buffers can be checked without a GPU.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct MeshBuffers {
+    pub positions: Vec<Vec2>,
+    // Every three indices are one counter clockwise triangle
+    pub indices: Vec<u32>,
+}
+
+impl MeshBuffers {
+    pub fn new() -> Self {
+        MeshBuffers::default()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    pub fn push_triangle(&mut self, a: Vec2, b: Vec2, c: Vec2) {
+        let first = self.positions.len() as u32;
+        // Flip clockwise triangles so every triangle faces the camera the same way
+        if (b - a).perp_dot(c - a) < 0.0 {
+            self.positions.extend([a, c, b]);
+        } else {
+            self.positions.extend([a, b, c]);
+        }
+        self.indices.extend([first, first + 1, first + 2]);
+    }
+
+    // A triangle between points that are already in the buffers, counter clockwise like push_triangle.
+    pub fn push_triangle_indices(&mut self, a: u32, b: u32, c: u32) {
+        let [point_a, point_b, point_c] = [a, b, c].map(|index| self.positions[index as usize]);
+        if (point_b - point_a).perp_dot(point_c - point_a) < 0.0 {
+            self.indices.extend([a, c, b]);
+        } else {
+            self.indices.extend([a, b, c]);
+        }
+    }
+
+    // A line `width` thick is a quad, two triangles.
+    // The quad sticks out half the width past both ends so lines meeting at a corner fill it in.
+    pub fn push_line(&mut self, start: Vec2, end: Vec2, width: f32) {
+        let along = (end - start).normalize_or_zero() * width / 2.0;
+        let side = along.perp();
+
+        let first = self.positions.len() as u32;
+        self.positions.extend([
+            start - along - side,
+            end + along - side,
+            end + along + side,
+            start - along + side,
+        ]);
+        self.indices
+            .extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+
+    // Draw a line between every point and the next, without closing the loop.
+    pub fn push_polyline(&mut self, points: &[Vec2], width: f32) {
+        for segment in points.windows(2) {
+            self.push_line(segment[0], segment[1], width);
+        }
+    }
+
+    // Draw every edge of a closed polygon as a line.
+    pub fn push_polygon_outline(&mut self, points: &[Vec2], width: f32) {
+        for (i, start) in points.iter().enumerate() {
+            self.push_line(*start, points[(i + 1) % points.len()], width);
+        }
+    }
+
+    // Fill a convex polygon by fanning triangles out from the first point.
+    // The triangles share the points, so every point is in the buffers once.
+    pub fn push_convex_polygon(&mut self, points: &[Vec2]) {
+        let first = self.positions.len() as u32;
+        self.positions.extend_from_slice(points);
+        for i in 1..points.len().saturating_sub(1) as u32 {
+            self.push_triangle_indices(first, first + i, first + i + 1);
+        }
+    }
+
+    // The outline of a closed polygon as one stroke `width` thick, centered on the edges.
+    // Unlike push_polygon_outline the edges share their corners instead of overlapping,
+    // so it looks right with see through colors, and `join` says how the corners are filled in.
+    // Corners that turn all the way back on themselves aren't supported.
+    pub fn push_closed_stroke(&mut self, points: &[Vec2], width: f32, join: LineJoin) {
+        let count = points.len();
+        if count < 3 {
+            return;
+        }
+        let half_width = width / 2.0;
+
+        // For every corner, the vertices where the edges going in and out of it end,
+        // on the left of the edges and on the right
+        let mut corners: Vec<([u32; 2], [u32; 2])> = Vec::with_capacity(count);
+        for (i, point) in points.iter().enumerate() {
+            let incoming = *point - points[(i + count - 1) % count];
+            let outgoing = points[(i + 1) % count] - *point;
+            let in_normal = incoming.normalize_or_zero().perp();
+            let out_normal = outgoing.normalize_or_zero().perp();
+
+            // Halfway between the normals, as far out as where both edges' sides meet
+            let miter = (in_normal + out_normal).normalize_or_zero();
+            let miter_length = half_width / miter.dot(in_normal);
+            let turn = incoming.perp_dot(outgoing);
+
+            let bevel = turn != 0.0
+                && match join {
+                    LineJoin::Miter { limit } => miter_length > limit * half_width,
+                    LineJoin::Bevel => true,
+                };
+
+            let first = self.positions.len() as u32;
+            if !bevel {
+                self.positions
+                    .extend([*point + miter * miter_length, *point - miter * miter_length]);
+                corners.push(([first, first], [first + 1, first + 1]));
+                continue;
+            }
+
+            // The inside of the turn still meets in a point, the outside is cut off
+            // with a triangle. Turning left the inside is on the left.
+            let side = if turn > 0.0 { 1.0 } else { -1.0 };
+            self.positions.extend([
+                *point + miter * miter_length * side,
+                *point - in_normal * half_width * side,
+                *point - out_normal * half_width * side,
+            ]);
+            self.push_triangle_indices(first, first + 1, first + 2);
+            if turn > 0.0 {
+                corners.push(([first, first], [first + 1, first + 2]));
+            } else {
+                corners.push(([first + 1, first + 2], [first, first]));
+            }
+        }
+
+        // Every edge is a quad from the end of one corner to the start of the next
+        for (i, (left, right)) in corners.iter().enumerate() {
+            let (next_left, next_right) = corners[(i + 1) % count];
+            self.push_triangle_indices(left[1], right[1], next_right[0]);
+            self.push_triangle_indices(left[1], next_right[0], next_left[0]);
+        }
+    }
+
+    // The outline of a circle made of `segments` straight lines.
+    pub fn push_circle_outline(&mut self, center: Vec2, radius: f32, width: f32, segments: u32) {
+        let segments = segments.max(3);
+        let points: Vec<Vec2> = (0..segments)
+            .map(|i| {
+                let angle = std::f32::consts::TAU * i as f32 / segments as f32;
+                center + Vec2::from_angle(angle) * radius
+            })
+            .collect();
+        self.push_polygon_outline(&points, width);
+    }
+
+    // Make a mesh the 2D pipeline can draw. It wants normals and uvs too,
+    // so every vertex gets a normal pointing at the screen.
+    pub fn into_mesh(self) -> Mesh {
+        let vertices: Vec<[f32; 3]> = self
+            .positions
+            .iter()
+            .map(|point| [point.x, point.y, 0.0])
+            .collect();
+        let normals = vec![[0.0, 0.0, 1.0]; self.positions.len()];
+        let uvs = vec![[0.0, 0.0]; self.positions.len()];
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        mesh.set_indices(Some(Indices::U32(self.indices)));
+        mesh
+    }
+}
+
+// Make a flat triangle mesh facing the camera out of 2D points and triangle indices.
+pub fn triangle_mesh(positions: &[Vec2], indices: Vec<u32>) -> Mesh {
+    MeshBuffers {
+        positions: positions.to_vec(),
+        indices,
+    }
+    .into_mesh()
+}
+
+// Fill a convex polygon by fanning triangles out from the first point.
+pub fn convex_polygon_mesh(points: &[Vec2]) -> Mesh {
+    let mut buffers = MeshBuffers::new();
+    buffers.push_convex_polygon(points);
+    buffers.into_mesh()
+}
+
+// Draw the edges of a closed polygon as lines `width` thick.
+pub fn polygon_outline_mesh(points: &[Vec2], width: f32) -> Mesh {
+    let mut buffers = MeshBuffers::new();
+    buffers.push_polygon_outline(points, width);
+    buffers.into_mesh()
+}
+
+// A slice of a circle pointing up the y axis, opening `half_angle` radians to both sides.
+// The arc is split into `segments` triangles that all meet at the origin.
+pub fn wedge_mesh(radius: f32, half_angle: f32, segments: u32) -> Mesh {
+    let segments = segments.max(1);
+    let mut points = vec![Vec2::ZERO];
+    for i in 0..=segments {
+        let angle = -half_angle + 2.0 * half_angle * i as f32 / segments as f32;
+        // Angles are measured from the y axis, so rotate a vector pointing up
+        points.push(Vec2::from_angle(angle).rotate(Vec2::Y) * radius);
+    }
+
+    let mut buffers = MeshBuffers::new();
+    buffers.push_convex_polygon(&points);
+    buffers.into_mesh()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_counter_clockwise(buffers: &MeshBuffers) {
+        for triangle in buffers.indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| buffers.positions[triangle[i] as usize]);
+            assert!((b - a).perp_dot(c - a) >= 0.0);
+        }
+    }
+
+    #[test]
+    fn line_is_a_quad() {
+        let mut buffers = MeshBuffers::new();
+        buffers.push_line(Vec2::ZERO, Vec2::new(10.0, 0.0), 2.0);
+        assert_eq!(buffers.positions.len(), 4);
+        assert_eq!(buffers.triangle_count(), 2);
+        assert!(buffers.positions.contains(&Vec2::new(11.0, 1.0)));
+        assert!(buffers.positions.contains(&Vec2::new(-1.0, -1.0)));
+        assert_counter_clockwise(&buffers);
+    }
+
+    #[test]
+    fn triangles_face_the_camera() {
+        let mut buffers = MeshBuffers::new();
+        buffers.push_triangle(Vec2::ZERO, Vec2::Y, Vec2::X);
+        buffers.push_convex_polygon(&[Vec2::ZERO, Vec2::Y, Vec2::ONE, Vec2::X]);
+        assert_eq!(buffers.triangle_count(), 3);
+        assert_counter_clockwise(&buffers);
+    }
+
+    #[test]
+    fn convex_polygon_shares_points() {
+        let mut buffers = MeshBuffers::new();
+        let square = [Vec2::ZERO, Vec2::X, Vec2::ONE, Vec2::Y];
+        buffers.push_convex_polygon(&square);
+        assert_eq!(buffers.positions, square);
+        assert_eq!(buffers.indices, [0, 1, 2, 0, 2, 3]);
+
+        // Clockwise points still make counter clockwise triangles
+        let mut clockwise = square;
+        clockwise.reverse();
+        buffers.push_convex_polygon(&clockwise);
+        assert_eq!(buffers.triangle_count(), 4);
+        assert_counter_clockwise(&buffers);
+    }
+
+    fn area(buffers: &MeshBuffers) -> f32 {
+        buffers
+            .indices
+            .chunks(3)
+            .map(|triangle| {
+                let [a, b, c] = [0, 1, 2].map(|i| buffers.positions[triangle[i] as usize]);
+                (b - a).perp_dot(c - a) / 2.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn mitered_stroke() {
+        let square = [
+            Vec2::ZERO,
+            Vec2::new(10.0, 0.0),
+            Vec2::new(10.0, 10.0),
+            Vec2::new(0.0, 10.0),
+        ];
+        let mut buffers = MeshBuffers::new();
+        buffers.push_closed_stroke(&square, 2.0, LineJoin::Miter { limit: 4.0 });
+
+        // Two vertices and one quad for every corner
+        assert_eq!(buffers.positions.len(), 8);
+        assert_eq!(buffers.triangle_count(), 8);
+        assert_counter_clockwise(&buffers);
+        // The corners are square, from -1 to 11 outside and 1 to 9 inside
+        for corner in [
+            Vec2::new(-1.0, -1.0),
+            Vec2::new(11.0, 11.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(9.0, 9.0),
+        ] {
+            assert!(buffers
+                .positions
+                .iter()
+                .any(|position| position.abs_diff_eq(corner, 1e-5)));
+        }
+        assert!((area(&buffers) - (12.0 * 12.0 - 8.0 * 8.0)).abs() < 1e-3);
+
+        // Going around the other way makes the same stroke
+        let mut clockwise = square;
+        clockwise.reverse();
+        let mut reversed = MeshBuffers::new();
+        reversed.push_closed_stroke(&clockwise, 2.0, LineJoin::Miter { limit: 4.0 });
+        assert_counter_clockwise(&reversed);
+        assert!((area(&reversed) - area(&buffers)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn beveled_stroke() {
+        let square = [
+            Vec2::ZERO,
+            Vec2::new(10.0, 0.0),
+            Vec2::new(10.0, 10.0),
+            Vec2::new(0.0, 10.0),
+        ];
+        let mut buffers = MeshBuffers::new();
+        buffers.push_closed_stroke(&square, 2.0, LineJoin::Bevel);
+
+        // Three vertices, a quad and a corner triangle for every corner
+        assert_eq!(buffers.positions.len(), 12);
+        assert_eq!(buffers.triangle_count(), 12);
+        assert_counter_clockwise(&buffers);
+        // Every corner is cut off by half a square of the half width
+        assert!((area(&buffers) - (12.0 * 12.0 - 8.0 * 8.0 - 4.0 * 0.5)).abs() < 1e-3);
+        assert!(!buffers.positions.contains(&Vec2::new(-1.0, -1.0)));
+
+        // A corner this sharp reaches further than the limit, so it gets beveled too
+        let sharp = [Vec2::ZERO, Vec2::new(10.0, 0.0), Vec2::new(0.0, 1.0)];
+        let mut buffers = MeshBuffers::new();
+        buffers.push_closed_stroke(&sharp, 0.5, LineJoin::Miter { limit: 4.0 });
+        assert_eq!(buffers.triangle_count(), 3 * 2 + 1);
+        assert_counter_clockwise(&buffers);
+    }
+
+    #[test]
+    fn shapes_share_buffers() {
+        let mut buffers = MeshBuffers::new();
+        buffers.push_line(Vec2::ZERO, Vec2::X, 1.0);
+        buffers.push_circle_outline(Vec2::ZERO, 5.0, 1.0, 16);
+        buffers.push_polyline(&[Vec2::ZERO, Vec2::X, Vec2::ONE], 1.0);
+        assert_eq!(buffers.triangle_count(), 2 + 16 * 2 + 2 * 2);
+        assert!(buffers
+            .indices
+            .iter()
+            .all(|&index| (index as usize) < buffers.positions.len()));
+    }
+}
@@ -0,0 +1,103 @@
+use bevy::prelude::*;
+
+// Moves every entity tagged with `Player` with WASD/arrow keys and, when a rotation speed is set,
+// rotates it with J/K.
+pub struct PlayerControllerPlugin {
+    // The player movement speed in 'pixels/second'.
+    pub speed: f32,
+    // The player rotation speed in 'radians/second'. Rotation is turned off when this is None.
+    pub rotation_speed: Option<f32>,
+}
+
+impl Default for PlayerControllerPlugin {
+    fn default() -> Self {
+        PlayerControllerPlugin {
+            speed: 300.0,
+            rotation_speed: None,
+        }
+    }
+}
+
+impl Plugin for PlayerControllerPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(PlayerSettings {
+            speed: self.speed,
+            rotation_speed: self.rotation_speed.unwrap_or(0.0),
+        })
+        .add_system(move_player);
+
+        if self.rotation_speed.is_some() {
+            app.add_system(rotate_player);
+        }
+    }
+}
+
+// The speeds the player systems read every frame, change this resource to tweak them at runtime.
+pub struct PlayerSettings {
+    pub speed: f32,
+    pub rotation_speed: f32,
+}
+
+#[derive(Component)]
+pub struct Player;
+
+// Get input from the keyboard (WASD) as a vector where every axis is -1, 0 or 1.
+pub fn movement_input(keyboard_input: &Input<KeyCode>) -> Vec2 {
+    let up: bool = keyboard_input.pressed(KeyCode::W) || keyboard_input.pressed(KeyCode::Up);
+    let down: bool = keyboard_input.pressed(KeyCode::S) || keyboard_input.pressed(KeyCode::Down);
+    let left: bool = keyboard_input.pressed(KeyCode::A) || keyboard_input.pressed(KeyCode::Left);
+    let right: bool = keyboard_input.pressed(KeyCode::D) || keyboard_input.pressed(KeyCode::Right);
+
+    // If left is pressed than it will be -1, right 1, both they cancel out.
+    let x_axis: i8 = -(left as i8) + right as i8;
+    let y_axis: i8 = -(down as i8) + up as i8;
+    Vec2::new(x_axis as f32, y_axis as f32)
+}
+
+// Get the rotation input from the keyboard, J turns counter clockwise and K clockwise.
+pub fn rotation_input(keyboard_input: &Input<KeyCode>) -> f32 {
+    let mut rotation_factor = 0.0;
+
+    if keyboard_input.pressed(KeyCode::J) {
+        rotation_factor += 1.0;
+    }
+
+    if keyboard_input.pressed(KeyCode::K) {
+        rotation_factor -= 1.0;
+    }
+
+    rotation_factor
+}
+
+pub fn move_player(
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<PlayerSettings>,
+    mut player_query: Query<&mut Transform, With<Player>>,
+    time: Res<Time>,
+) {
+    let move_delta = movement_input(&keyboard_input);
+
+    // move the player
+    let delta_time = time.delta_seconds();
+    for mut transform in player_query.iter_mut() {
+        transform.translation.x += move_delta.x * settings.speed * delta_time;
+        transform.translation.y += move_delta.y * settings.speed * delta_time;
+    }
+}
+
+pub fn rotate_player(
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<PlayerSettings>,
+    mut player_query: Query<&mut Transform, With<Player>>,
+    time: Res<Time>,
+) {
+    let rotation_factor = rotation_input(&keyboard_input);
+
+    // update the player rotation around the Z axis (perpendicular to the 2D plane of the screen)
+    let delta_time = time.delta_seconds();
+    for mut transform in player_query.iter_mut() {
+        transform.rotate(Quat::from_rotation_z(
+            rotation_factor * settings.rotation_speed * delta_time,
+        ));
+    }
+}
@@ -0,0 +1,254 @@
+use bevy::math::Vec2;
+
+use crate::sdf::sdf_segment;
+
+// Point in polygon tests that work for any polygon, concave or crossing itself.
+// The polygon is a list of points where the last point connects back to the first.
+
+// Points closer than this to an edge are on the edge.
+pub const ON_EDGE_DISTANCE: f32 = 1e-4;
+
+// How to decide what is inside a polygon that winds around a point more than once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FillRule {
+    // Inside when a ray from the point crosses the edges an odd number of times.
+    // The middle of a star drawn in one line is outside with this rule.
+    EvenOdd,
+    // Inside when the polygon winds around the point at least once.
+    NonZero,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointClass {
+    Inside,
+    Outside,
+    OnEdge,
+}
+
+// How many times the polygon winds around the point, counter clockwise is positive.
+// Walk over every edge and count the ones crossing the horizontal line through the point,
+// edges right of the point going up add one and going down take one away.
+pub fn winding_number(point: Vec2, polygon: &[Vec2]) -> i32 {
+    let mut winding = 0;
+
+    for (i, start) in polygon.iter().enumerate() {
+        let end = polygon[(i + 1) % polygon.len()];
+        // The 2D cross product is positive when the point is left of the edge
+        let side = (end - *start).perp_dot(point - *start);
+
+        if start.y <= point.y {
+            if end.y > point.y && side > 0.0 {
+                winding += 1;
+            }
+        } else if end.y <= point.y && side < 0.0 {
+            winding -= 1;
+        }
+    }
+
+    winding
+}
+
+// Is the point on the border of the polygon.
+pub fn point_on_polygon_edge(point: Vec2, polygon: &[Vec2]) -> bool {
+    polygon.iter().enumerate().any(|(i, start)| {
+        let end = polygon[(i + 1) % polygon.len()];
+        sdf_segment(point, *start, end) <= ON_EDGE_DISTANCE
+    })
+}
+
+// Decide if the point is inside, outside or on the edge of the polygon using the fill rule.
+pub fn classify_point(point: Vec2, polygon: &[Vec2], fill_rule: FillRule) -> PointClass {
+    if polygon.len() < 3 {
+        return PointClass::Outside;
+    }
+
+    if point_on_polygon_edge(point, polygon) {
+        return PointClass::OnEdge;
+    }
+
+    let winding = winding_number(point, polygon);
+    let inside = match fill_rule {
+        // The number of crossings is odd exactly when the winding number is odd
+        FillRule::EvenOdd => winding % 2 != 0,
+        FillRule::NonZero => winding != 0,
+    };
+
+    if inside {
+        PointClass::Inside
+    } else {
+        PointClass::Outside
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::containment::{point_in_aabb, point_in_convex_polygon};
+
+    fn square() -> Vec<Vec2> {
+        vec![
+            Vec2::new(-50.0, -50.0),
+            Vec2::new(50.0, -50.0),
+            Vec2::new(50.0, 50.0),
+            Vec2::new(-50.0, 50.0),
+        ]
+    }
+
+    // A U shape with the opening at the top
+    fn u_shape() -> Vec<Vec2> {
+        vec![
+            Vec2::new(-60.0, -60.0),
+            Vec2::new(60.0, -60.0),
+            Vec2::new(60.0, 60.0),
+            Vec2::new(20.0, 60.0),
+            Vec2::new(20.0, -20.0),
+            Vec2::new(-20.0, -20.0),
+            Vec2::new(-20.0, 60.0),
+            Vec2::new(-60.0, 60.0),
+        ]
+    }
+
+    // A five pointed star drawn in one line by visiting every second corner of a pentagon
+    fn pentagram() -> Vec<Vec2> {
+        (0..5)
+            .map(|i| Vec2::from_angle(i as f32 * 2.0 * std::f32::consts::TAU / 5.0) * 100.0)
+            .collect()
+    }
+
+    #[test]
+    fn winding_direction() {
+        let mut polygon = square();
+        assert_eq!(winding_number(Vec2::ZERO, &polygon), 1);
+        polygon.reverse();
+        assert_eq!(winding_number(Vec2::ZERO, &polygon), -1);
+        assert_eq!(winding_number(Vec2::new(80.0, 0.0), &polygon), 0);
+    }
+
+    #[test]
+    fn square_matches_aabb() {
+        for x in -30..=30 {
+            for y in -30..=30 {
+                let point = Vec2::new(x as f32 * 3.3, y as f32 * 3.3);
+                let expected = if point_on_polygon_edge(point, &square()) {
+                    PointClass::OnEdge
+                } else if point_in_aabb(point, Vec2::ZERO, Vec2::splat(50.0)) {
+                    PointClass::Inside
+                } else {
+                    PointClass::Outside
+                };
+                for fill_rule in [FillRule::EvenOdd, FillRule::NonZero] {
+                    assert_eq!(classify_point(point, &square(), fill_rule), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn concave() {
+        let polygon = u_shape();
+        for fill_rule in [FillRule::EvenOdd, FillRule::NonZero] {
+            // The arms and the bottom
+            assert_eq!(
+                classify_point(Vec2::new(-40.0, 40.0), &polygon, fill_rule),
+                PointClass::Inside
+            );
+            assert_eq!(
+                classify_point(Vec2::new(40.0, 40.0), &polygon, fill_rule),
+                PointClass::Inside
+            );
+            assert_eq!(
+                classify_point(Vec2::new(0.0, -40.0), &polygon, fill_rule),
+                PointClass::Inside
+            );
+            // The gap between the arms, which a convex test gets wrong
+            let gap = Vec2::new(0.0, 20.0);
+            assert_eq!(
+                classify_point(gap, &polygon, fill_rule),
+                PointClass::Outside
+            );
+            assert!(point_in_convex_polygon(
+                gap,
+                &[polygon[0], polygon[1], polygon[2], polygon[7]]
+            ));
+            // Level with the inner corners, where the ray passes through vertices
+            assert_eq!(
+                classify_point(Vec2::new(-40.0, -20.0), &polygon, fill_rule),
+                PointClass::Inside
+            );
+            assert_eq!(
+                classify_point(Vec2::new(-80.0, -20.0), &polygon, fill_rule),
+                PointClass::Outside
+            );
+            assert_eq!(
+                classify_point(Vec2::new(-80.0, 60.0), &polygon, fill_rule),
+                PointClass::Outside
+            );
+        }
+    }
+
+    #[test]
+    fn self_intersecting() {
+        let polygon = pentagram();
+        // The middle pentagon is wound twice
+        assert_eq!(winding_number(Vec2::ZERO, &polygon), 2);
+        assert_eq!(
+            classify_point(Vec2::ZERO, &polygon, FillRule::NonZero),
+            PointClass::Inside
+        );
+        assert_eq!(
+            classify_point(Vec2::ZERO, &polygon, FillRule::EvenOdd),
+            PointClass::Outside
+        );
+
+        // The tips are wound once so both rules agree
+        let tip = Vec2::new(80.0, 0.0);
+        assert_eq!(winding_number(tip, &polygon), 1);
+        assert_eq!(
+            classify_point(tip, &polygon, FillRule::NonZero),
+            PointClass::Inside
+        );
+        assert_eq!(
+            classify_point(tip, &polygon, FillRule::EvenOdd),
+            PointClass::Inside
+        );
+
+        assert_eq!(
+            classify_point(Vec2::new(150.0, 0.0), &polygon, FillRule::NonZero),
+            PointClass::Outside
+        );
+    }
+
+    #[test]
+    fn on_edge() {
+        let polygon = u_shape();
+        for point in [
+            // Corners
+            polygon[0],
+            polygon[4],
+            // Middle of the bottom edge and of an inner edge
+            Vec2::new(0.0, -60.0),
+            Vec2::new(20.0, 10.0),
+        ] {
+            for fill_rule in [FillRule::EvenOdd, FillRule::NonZero] {
+                assert_eq!(
+                    classify_point(point, &polygon, fill_rule),
+                    PointClass::OnEdge
+                );
+            }
+        }
+
+        let just_off = Vec2::new(0.0, -60.0 - ON_EDGE_DISTANCE * 10.0);
+        assert_eq!(
+            classify_point(just_off, &polygon, FillRule::NonZero),
+            PointClass::Outside
+        );
+    }
+
+    #[test]
+    fn too_few_points() {
+        assert_eq!(
+            classify_point(Vec2::ZERO, &[Vec2::ZERO, Vec2::X], FillRule::NonZero),
+            PointClass::Outside
+        );
+    }
+}
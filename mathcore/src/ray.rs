@@ -0,0 +1,444 @@
+use bevy::prelude::*;
+
+// Ray intersections without a physics engine. Every function works like rapier's
+// `cast_ray_and_get_normal`: the ray is `origin + direction * toi` and only hits with
+// `toi` up to `max_toi` count, so `toi` is a distance when `direction` is unit length.
+//
+// `solid` says what happens to a ray starting inside a shape. A solid shape is hit straight away,
+// with a toi of 0 and no normal. Otherwise the ray goes on to hit the shape's boundary on the way out.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayIntersection {
+    pub toi: f32,
+    // Pointing out of the shape, or for a segment back towards the ray
+    pub normal: Vec2,
+}
+
+// Pick the hit from where the ray enters and leaves a shape, `enter` before `exit`.
+fn enter_or_exit(
+    enter: (f32, Vec2),
+    exit: (f32, Vec2),
+    max_toi: f32,
+    solid: bool,
+) -> Option<RayIntersection> {
+    let (toi, normal) = if enter.0 >= 0.0 {
+        enter
+    } else if exit.0 < 0.0 {
+        // The whole shape is behind the ray
+        return None;
+    } else if solid {
+        (0.0, Vec2::ZERO)
+    } else {
+        exit
+    };
+
+    if toi <= max_toi {
+        Some(RayIntersection { toi, normal })
+    } else {
+        None
+    }
+}
+
+// A segment has no inside, so the normal is whichever side faces the ray.
+pub fn ray_segment(
+    origin: Vec2,
+    direction: Vec2,
+    max_toi: f32,
+    start: Vec2,
+    end: Vec2,
+) -> Option<RayIntersection> {
+    let edge = end - start;
+    let denominator = direction.perp_dot(edge);
+    // Parallel, they never cross
+    if denominator == 0.0 {
+        return None;
+    }
+
+    // Solve origin + direction * toi = start + edge * along for toi and along
+    let to_start = start - origin;
+    let toi = to_start.perp_dot(edge) / denominator;
+    let along = to_start.perp_dot(direction) / denominator;
+    if toi < 0.0 || toi > max_toi || !(0.0..=1.0).contains(&along) {
+        return None;
+    }
+
+    let mut normal = edge.perp().normalize();
+    if normal.dot(direction) > 0.0 {
+        normal = -normal;
+    }
+    Some(RayIntersection { toi, normal })
+}
+
+// The slab method. An axis aligned box is where the slab between its left and right sides
+// overlaps the slab between its bottom and top. The ray is inside each slab for a range of toi,
+// so it is inside the box from the latest slab entry to the earliest slab exit.
+pub fn ray_aabb(
+    origin: Vec2,
+    direction: Vec2,
+    max_toi: f32,
+    center: Vec2,
+    half_extents: Vec2,
+    solid: bool,
+) -> Option<RayIntersection> {
+    let min = center - half_extents;
+    let max = center + half_extents;
+
+    let mut enter = (f32::NEG_INFINITY, Vec2::ZERO);
+    let mut exit = (f32::INFINITY, Vec2::ZERO);
+
+    for (axis, axis_direction) in [(0, Vec2::X), (1, Vec2::Y)] {
+        if direction[axis] == 0.0 {
+            // Parallel to this slab, it is either always in it or never
+            if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                return None;
+            }
+            continue;
+        }
+
+        let mut near = (
+            (min[axis] - origin[axis]) / direction[axis],
+            -axis_direction,
+        );
+        let mut far = ((max[axis] - origin[axis]) / direction[axis], axis_direction);
+        if near.0 > far.0 {
+            std::mem::swap(&mut near, &mut far);
+        }
+
+        if near.0 > enter.0 {
+            enter = near;
+        }
+        if far.0 < exit.0 {
+            exit = far;
+        }
+    }
+
+    if enter.0 > exit.0 {
+        return None;
+    }
+    enter_or_exit(enter, exit, max_toi, solid)
+}
+
+// An oriented box is an axis aligned box in its own space, so turn the ray into that space,
+// use the slab method, and turn the normal back out.
+pub fn ray_obb(
+    origin: Vec2,
+    direction: Vec2,
+    max_toi: f32,
+    center: Vec2,
+    half_extents: Vec2,
+    rotation: f32,
+    solid: bool,
+) -> Option<RayIntersection> {
+    let to_local = Vec2::from_angle(-rotation);
+    let local_origin = to_local.rotate(origin - center);
+    let local_direction = to_local.rotate(direction);
+
+    ray_aabb(
+        local_origin,
+        local_direction,
+        max_toi,
+        Vec2::ZERO,
+        half_extents,
+        solid,
+    )
+    .map(|hit| RayIntersection {
+        toi: hit.toi,
+        normal: Vec2::from_angle(rotation).rotate(hit.normal),
+    })
+}
+
+// Where the distance from the ray to the center is the radius,
+// |origin + direction * toi - center|² = radius², a quadratic in toi.
+pub fn ray_circle(
+    origin: Vec2,
+    direction: Vec2,
+    max_toi: f32,
+    center: Vec2,
+    radius: f32,
+    solid: bool,
+) -> Option<RayIntersection> {
+    let offset = origin - center;
+    let a = direction.length_squared();
+    let half_b = offset.dot(direction);
+    let c = offset.length_squared() - radius * radius;
+
+    let discriminant = half_b * half_b - a * c;
+    if a == 0.0 || discriminant < 0.0 {
+        return None;
+    }
+
+    let root = discriminant.sqrt();
+    let normal_at = |toi: f32| (offset + direction * toi).normalize_or_zero();
+    let enter_toi = (-half_b - root) / a;
+    let exit_toi = (-half_b + root) / a;
+    enter_or_exit(
+        (enter_toi, normal_at(enter_toi)),
+        (exit_toi, normal_at(exit_toi)),
+        max_toi,
+        solid,
+    )
+}
+
+// Cyrus-Beck clipping. A convex polygon is where the inside half of every edge overlaps,
+// so it works like the slab method with one half plane per edge.
+// The points can go around either way.
+pub fn ray_convex_polygon(
+    origin: Vec2,
+    direction: Vec2,
+    max_toi: f32,
+    points: &[Vec2],
+    solid: bool,
+) -> Option<RayIntersection> {
+    if points.len() < 3 {
+        return None;
+    }
+
+    // Twice the signed area, positive when the points go counter clockwise
+    let area: f32 = (0..points.len())
+        .map(|i| points[i].perp_dot(points[(i + 1) % points.len()]))
+        .sum();
+
+    let mut enter = (f32::NEG_INFINITY, Vec2::ZERO);
+    let mut exit = (f32::INFINITY, Vec2::ZERO);
+
+    for (i, start) in points.iter().enumerate() {
+        let edge = points[(i + 1) % points.len()] - *start;
+        let mut normal = Vec2::new(edge.y, -edge.x).normalize_or_zero();
+        if area < 0.0 {
+            normal = -normal;
+        }
+
+        // How far outside this edge the origin is, and how fast the ray heads out of it
+        let outside = normal.dot(origin - *start);
+        let speed = normal.dot(direction);
+        if speed == 0.0 {
+            if outside > 0.0 {
+                return None;
+            }
+            continue;
+        }
+
+        let toi = -outside / speed;
+        if speed < 0.0 {
+            if toi > enter.0 {
+                enter = (toi, normal);
+            }
+        } else if toi < exit.0 {
+            exit = (toi, normal);
+        }
+    }
+
+    if enter.0 > exit.0 {
+        return None;
+    }
+    enter_or_exit(enter, exit, max_toi, solid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-3;
+
+    fn assert_hit(hit: Option<RayIntersection>, toi: f32, normal: Vec2) {
+        let hit = hit.expect("should have hit");
+        assert!(
+            (hit.toi - toi).abs() < EPSILON,
+            "toi {} is not {}",
+            hit.toi,
+            toi
+        );
+        assert!(
+            hit.normal.abs_diff_eq(normal, EPSILON),
+            "normal {} is not {}",
+            hit.normal,
+            normal
+        );
+    }
+
+    #[test]
+    fn segment() {
+        let (start, end) = (Vec2::new(10.0, -5.0), Vec2::new(10.0, 5.0));
+        assert_hit(
+            ray_segment(Vec2::ZERO, Vec2::X, 100.0, start, end),
+            10.0,
+            Vec2::NEG_X,
+        );
+        // From the other side the normal flips
+        assert_hit(
+            ray_segment(Vec2::new(20.0, 0.0), Vec2::NEG_X, 100.0, start, end),
+            10.0,
+            Vec2::X,
+        );
+        // Past the end, parallel, too short and behind
+        assert_eq!(
+            ray_segment(Vec2::new(0.0, 6.0), Vec2::X, 100.0, start, end),
+            None
+        );
+        assert_eq!(ray_segment(Vec2::ZERO, Vec2::Y, 100.0, start, end), None);
+        assert_eq!(ray_segment(Vec2::ZERO, Vec2::X, 9.0, start, end), None);
+        assert_eq!(
+            ray_segment(Vec2::ZERO, Vec2::NEG_X, 100.0, start, end),
+            None
+        );
+    }
+
+    #[test]
+    fn aabb() {
+        let center = Vec2::new(50.0, 0.0);
+        let half = Vec2::new(10.0, 20.0);
+        assert_hit(
+            ray_aabb(Vec2::ZERO, Vec2::X, 100.0, center, half, true),
+            40.0,
+            Vec2::NEG_X,
+        );
+        // toi counts in lengths of the direction
+        assert_hit(
+            ray_aabb(Vec2::ZERO, Vec2::X * 2.0, 100.0, center, half, true),
+            20.0,
+            Vec2::NEG_X,
+        );
+        assert_hit(
+            ray_aabb(
+                Vec2::new(50.0, 100.0),
+                Vec2::NEG_Y,
+                100.0,
+                center,
+                half,
+                true,
+            ),
+            80.0,
+            Vec2::Y,
+        );
+        assert_eq!(
+            ray_aabb(Vec2::ZERO, Vec2::Y, 100.0, center, half, true),
+            None
+        );
+        assert_eq!(
+            ray_aabb(Vec2::ZERO, Vec2::X, 39.0, center, half, true),
+            None
+        );
+        assert_eq!(
+            ray_aabb(Vec2::ZERO, Vec2::NEG_X, 100.0, center, half, true),
+            None
+        );
+
+        // Starting inside
+        assert_hit(
+            ray_aabb(center, Vec2::Y, 100.0, center, half, true),
+            0.0,
+            Vec2::ZERO,
+        );
+        assert_hit(
+            ray_aabb(center, Vec2::Y, 100.0, center, half, false),
+            20.0,
+            Vec2::Y,
+        );
+    }
+
+    #[test]
+    fn obb() {
+        // Turned a quarter turn, the box is 40 wide and 20 tall
+        let rotation = std::f32::consts::FRAC_PI_2;
+        let center = Vec2::new(50.0, 0.0);
+        let half = Vec2::new(10.0, 20.0);
+        assert_hit(
+            ray_obb(Vec2::ZERO, Vec2::X, 100.0, center, half, rotation, true),
+            30.0,
+            Vec2::NEG_X,
+        );
+
+        // 45 degrees, the corner points at the ray
+        let rotation = std::f32::consts::FRAC_PI_4;
+        let half = Vec2::new(10.0, 10.0);
+        let hit = ray_obb(Vec2::ZERO, Vec2::X, 100.0, center, half, rotation, true).unwrap();
+        assert!((hit.toi - (50.0 - 10.0 * std::f32::consts::SQRT_2)).abs() < EPSILON);
+
+        // Without a rotation it is the same as an aabb
+        for direction in [Vec2::X, Vec2::new(1.0, 0.3), Vec2::new(1.0, -0.2)] {
+            assert_eq!(
+                ray_obb(Vec2::ZERO, direction, 100.0, center, half, 0.0, false),
+                ray_aabb(Vec2::ZERO, direction, 100.0, center, half, false)
+            );
+        }
+    }
+
+    #[test]
+    fn circle() {
+        let center = Vec2::new(50.0, 0.0);
+        assert_hit(
+            ray_circle(Vec2::ZERO, Vec2::X, 100.0, center, 10.0, true),
+            40.0,
+            Vec2::NEG_X,
+        );
+        // Grazing the top
+        assert_hit(
+            ray_circle(Vec2::new(0.0, 10.0), Vec2::X, 100.0, center, 10.0, true),
+            50.0,
+            Vec2::Y,
+        );
+        assert_eq!(
+            ray_circle(Vec2::new(0.0, 11.0), Vec2::X, 100.0, center, 10.0, true),
+            None
+        );
+        assert_hit(
+            ray_circle(center, Vec2::NEG_Y, 100.0, center, 10.0, false),
+            10.0,
+            Vec2::NEG_Y,
+        );
+        assert_hit(
+            ray_circle(center, Vec2::NEG_Y, 100.0, center, 10.0, true),
+            0.0,
+            Vec2::ZERO,
+        );
+    }
+
+    #[test]
+    fn convex_polygon() {
+        let triangle = [
+            Vec2::new(40.0, -10.0),
+            Vec2::new(60.0, -10.0),
+            Vec2::new(50.0, 10.0),
+        ];
+        assert_hit(
+            ray_convex_polygon(Vec2::new(50.0, -50.0), Vec2::Y, 100.0, &triangle, true),
+            40.0,
+            Vec2::NEG_Y,
+        );
+
+        // Either winding gives the same answers
+        let mut clockwise = triangle;
+        clockwise.reverse();
+        for direction in [Vec2::X, Vec2::new(1.0, 0.1), Vec2::new(1.0, -0.15)] {
+            assert_eq!(
+                ray_convex_polygon(Vec2::ZERO, direction, 100.0, &triangle, false),
+                ray_convex_polygon(Vec2::ZERO, direction, 100.0, &clockwise, false)
+            );
+        }
+
+        // A square polygon is the same as the box
+        let center = Vec2::new(50.0, 0.0);
+        let half = Vec2::new(10.0, 20.0);
+        let square = [
+            center - half,
+            center + Vec2::new(half.x, -half.y),
+            center + half,
+            center + Vec2::new(-half.x, half.y),
+        ];
+        for (origin, direction) in [
+            (Vec2::ZERO, Vec2::new(1.0, 0.2)),
+            (Vec2::new(50.0, 100.0), Vec2::new(0.1, -1.0)),
+            (center, Vec2::new(-0.3, 1.0)),
+        ] {
+            for solid in [true, false] {
+                let polygon_hit = ray_convex_polygon(origin, direction, 200.0, &square, solid);
+                let box_hit = ray_aabb(origin, direction, 200.0, center, half, solid);
+                assert_eq!(polygon_hit.is_some(), box_hit.is_some());
+                if let (Some(polygon_hit), Some(box_hit)) = (polygon_hit, box_hit) {
+                    assert!((polygon_hit.toi - box_hit.toi).abs() < EPSILON);
+                    assert!(polygon_hit.normal.abs_diff_eq(box_hit.normal, EPSILON));
+                }
+            }
+        }
+    }
+}
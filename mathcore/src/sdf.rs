@@ -0,0 +1,291 @@
+use bevy::math::Vec2;
+use std::f32::consts::PI;
+
+// Signed distance functions for 2D shapes.
+// Every function returns how far the point is from the border of the shape,
+// negative inside the shape, positive outside and zero on the border.
+// The shapes sit at the origin, so move the point into the shape's local space before calling them.
+// Most of these are the ones from https://iquilezles.org/articles/distfunctions2d/
+
+// A circle is the distance to the center minus the radius.
+pub fn sdf_circle(point: Vec2, radius: f32) -> f32 {
+    point.length() - radius
+}
+
+// A box with half width and half height `half_extents`.
+// Because of symmetry only the top right corner is needed, so the point is folded into it with abs.
+pub fn sdf_box(point: Vec2, half_extents: Vec2) -> f32 {
+    let d = point.abs() - half_extents;
+    // Outside it is the distance to the closest corner or edge, inside it is the closest edge
+    let outside = d.max(Vec2::ZERO).length();
+    let inside = d.x.max(d.y).min(0.0);
+    outside + inside
+}
+
+// A box with round corners, the corners have `corner_radius` and the box still fits in `half_extents`.
+// It is a smaller box that is grown by the radius in every direction.
+pub fn sdf_rounded_box(point: Vec2, half_extents: Vec2, corner_radius: f32) -> f32 {
+    sdf_box(point, half_extents - Vec2::splat(corner_radius)) - corner_radius
+}
+
+// A line segment from start to end has no inside, so it is never negative.
+pub fn sdf_segment(point: Vec2, start: Vec2, end: Vec2) -> f32 {
+    let start_to_point = point - start;
+    let start_to_end = end - start;
+
+    // Project the point on the line and clamp it so the closest point stays on the segment
+    let length_squared = start_to_end.length_squared();
+    let t = if length_squared > 0.0 {
+        (start_to_point.dot(start_to_end) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+
+    (start_to_point - start_to_end * t).length()
+}
+
+// A capsule is a segment grown by a radius.
+pub fn sdf_capsule(point: Vec2, start: Vec2, end: Vec2, radius: f32) -> f32 {
+    sdf_segment(point, start, end) - radius
+}
+
+// A regular polygon with `sides` corners that are `radius` away from the center.
+// The first corner is at `rotation` radians from the x axis, like Vec2::from_angle.
+pub fn sdf_regular_polygon(point: Vec2, radius: f32, sides: u32, rotation: f32) -> f32 {
+    // Half the angle between two corners
+    let half_angle = PI / sides as f32;
+
+    // Fold the point into one half of one side. The middle of that side is on the x axis
+    // and its corner is at half_angle above it.
+    let angle = (point.y.atan2(point.x) - rotation).rem_euclid(2.0 * half_angle) - half_angle;
+    let folded = point.length() * Vec2::new(angle.cos(), angle.sin().abs());
+
+    // Distance to the half side going from the corner down to the x axis
+    let corner = radius * Vec2::new(half_angle.cos(), half_angle.sin());
+    let mut corner_to_point = folded - corner;
+    corner_to_point.y += (-corner_to_point.y).clamp(0.0, corner.y);
+
+    // Right of the side is outside
+    corner_to_point.length() * corner_to_point.x.signum()
+}
+
+// A triangle with the corners a, b and c in either winding order.
+pub fn sdf_triangle(point: Vec2, a: Vec2, b: Vec2, c: Vec2) -> f32 {
+    let edges = [(a, b - a), (b, c - b), (c, a - c)];
+    // Which way the triangle winds so the inside is always negative
+    let winding = (b - a).perp_dot(c - a).signum();
+
+    let mut distance_squared = f32::MAX;
+    let mut side = f32::MAX;
+    for (start, edge) in edges {
+        let start_to_point = point - start;
+        let closest = edge * (start_to_point.dot(edge) / edge.length_squared()).clamp(0.0, 1.0);
+
+        distance_squared = distance_squared.min((start_to_point - closest).length_squared());
+        // Positive when the point is on the inside of this edge
+        side = side.min(winding * edge.perp_dot(start_to_point));
+    }
+
+    // The point is inside only when it is on the inside of every edge
+    -distance_squared.sqrt() * side.signum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::containment::{
+        point_in_aabb, point_in_circle, point_in_convex_polygon, point_in_obb,
+    };
+    use crate::test_support::Lcg;
+
+    // Points closer than this to the border can round either way, so the sign checks skip them
+    const BORDER: f32 = 1e-3;
+
+    fn sample_points() -> Vec<Vec2> {
+        let mut random = Lcg(7);
+        (0..5000)
+            .map(|_| random.next_point(-150.0, 150.0))
+            .collect()
+    }
+
+    // Negative exactly when the point is in the shape
+    fn assert_sign_agrees(sdf: impl Fn(Vec2) -> f32, inside: impl Fn(Vec2) -> bool) {
+        for point in sample_points() {
+            let distance = sdf(point);
+            if distance.abs() > BORDER {
+                assert_eq!(
+                    distance < 0.0,
+                    inside(point),
+                    "point {} distance {}",
+                    point,
+                    distance
+                );
+            }
+        }
+    }
+
+    // Moving a point never changes the distance by more than how far it moved
+    fn assert_lipschitz(sdf: impl Fn(Vec2) -> f32) {
+        let mut random = Lcg(11);
+        for point in sample_points() {
+            let other = point + random.next_point(-20.0, 20.0);
+            let change = (sdf(point) - sdf(other)).abs();
+            assert!(
+                change <= point.distance(other) + BORDER,
+                "{} to {} changed by {}",
+                point,
+                other,
+                change
+            );
+        }
+    }
+
+    fn regular_polygon(radius: f32, sides: u32, rotation: f32) -> Vec<Vec2> {
+        (0..sides)
+            .map(|i| Vec2::from_angle(rotation + i as f32 * 2.0 * PI / sides as f32) * radius)
+            .collect()
+    }
+
+    #[test]
+    fn circle() {
+        assert_eq!(sdf_circle(Vec2::new(30.0, 40.0), 20.0), 30.0);
+        assert_eq!(sdf_circle(Vec2::ZERO, 20.0), -20.0);
+        assert_sign_agrees(
+            |p| sdf_circle(p, 70.0),
+            |p| point_in_circle(p, Vec2::ZERO, 70.0),
+        );
+        assert_lipschitz(|p| sdf_circle(p, 70.0));
+    }
+
+    #[test]
+    fn boxes() {
+        let half = Vec2::new(80.0, 30.0);
+        assert_eq!(sdf_box(Vec2::new(100.0, 0.0), half), 20.0);
+        assert_eq!(sdf_box(Vec2::new(0.0, 10.0), half), -20.0);
+        // Outside a corner it is the distance to the corner
+        assert_eq!(sdf_box(Vec2::new(83.0, 34.0), half), 5.0);
+        assert_sign_agrees(|p| sdf_box(p, half), |p| point_in_aabb(p, Vec2::ZERO, half));
+        assert_lipschitz(|p| sdf_box(p, half));
+    }
+
+    #[test]
+    fn rotated_box() {
+        let half = Vec2::new(80.0, 30.0);
+        let rotation = f32::to_radians(35.0);
+        let to_local = |p: Vec2| Vec2::from_angle(-rotation).rotate(p);
+        assert_sign_agrees(
+            |p| sdf_box(to_local(p), half),
+            |p| point_in_obb(p, Vec2::ZERO, half, rotation),
+        );
+        assert_lipschitz(|p| sdf_box(to_local(p), half));
+    }
+
+    #[test]
+    fn rounded_box() {
+        let half = Vec2::new(80.0, 50.0);
+        let corner_radius = 20.0;
+        let inner = half - Vec2::splat(corner_radius);
+
+        // A rounded box is two boxes crossing each other plus a circle in each corner
+        let inside = |p: Vec2| {
+            point_in_aabb(p, Vec2::ZERO, Vec2::new(inner.x, half.y))
+                || point_in_aabb(p, Vec2::ZERO, Vec2::new(half.x, inner.y))
+                || point_in_circle(p.abs(), inner, corner_radius)
+        };
+        assert_sign_agrees(|p| sdf_rounded_box(p, half, corner_radius), inside);
+        assert_lipschitz(|p| sdf_rounded_box(p, half, corner_radius));
+
+        // With no radius it is just a box
+        for point in sample_points() {
+            assert_eq!(sdf_rounded_box(point, half, 0.0), sdf_box(point, half));
+        }
+    }
+
+    #[test]
+    fn segment() {
+        let start = Vec2::new(-50.0, -20.0);
+        let end = Vec2::new(60.0, 40.0);
+        assert_eq!(sdf_segment(start, start, end), 0.0);
+        assert_eq!(sdf_segment((start + end) / 2.0, start, end), 0.0);
+        assert_eq!(sdf_segment(Vec2::new(-50.0, -30.0), start, end), 10.0);
+        for point in sample_points() {
+            assert!(sdf_segment(point, start, end) >= 0.0);
+        }
+        assert_lipschitz(|p| sdf_segment(p, start, end));
+        // A segment with no length is a point
+        assert_eq!(
+            sdf_segment(Vec2::new(3.0, 4.0), Vec2::ZERO, Vec2::ZERO),
+            5.0
+        );
+    }
+
+    #[test]
+    fn capsule() {
+        let start = Vec2::new(-60.0, 0.0);
+        let end = Vec2::new(60.0, 0.0);
+        let radius = 25.0;
+
+        // A capsule is a box between the two end circles
+        let inside = |p: Vec2| {
+            point_in_aabb(p, Vec2::ZERO, Vec2::new(60.0, radius))
+                || point_in_circle(p, start, radius)
+                || point_in_circle(p, end, radius)
+        };
+        assert_sign_agrees(|p| sdf_capsule(p, start, end, radius), inside);
+        assert_lipschitz(|p| sdf_capsule(p, start, end, radius));
+    }
+
+    #[test]
+    fn regular_polygon_sign() {
+        for sides in 3..=8 {
+            let rotation = 0.3 * sides as f32;
+            let corners = regular_polygon(100.0, sides, rotation);
+            assert_sign_agrees(
+                |p| sdf_regular_polygon(p, 100.0, sides, rotation),
+                |p| point_in_convex_polygon(p, &corners),
+            );
+            assert_lipschitz(|p| sdf_regular_polygon(p, 100.0, sides, rotation));
+
+            // The corners are on the border
+            for corner in corners {
+                assert!(sdf_regular_polygon(corner, 100.0, sides, rotation).abs() < BORDER);
+            }
+        }
+    }
+
+    #[test]
+    fn regular_polygon_distance() {
+        // A square with corners on the axes has its sides cos(45) * radius from the center
+        let apothem = 100.0 * f32::to_radians(45.0).cos();
+        let distance = sdf_regular_polygon(Vec2::ZERO, 100.0, 4, 0.0);
+        assert!((distance + apothem).abs() < BORDER * 10.0);
+        // Past a corner it is the distance to that corner
+        let distance = sdf_regular_polygon(Vec2::new(130.0, 0.0), 100.0, 4, 0.0);
+        assert!((distance - 30.0).abs() < BORDER * 10.0);
+    }
+
+    #[test]
+    fn triangle() {
+        let a = Vec2::new(-80.0, -60.0);
+        let b = Vec2::new(90.0, -40.0);
+        let c = Vec2::new(10.0, 100.0);
+
+        for (a, b, c) in [(a, b, c), (c, b, a)] {
+            assert_sign_agrees(
+                |p| sdf_triangle(p, a, b, c),
+                |p| point_in_convex_polygon(p, &[a, b, c]),
+            );
+            assert_lipschitz(|p| sdf_triangle(p, a, b, c));
+        }
+
+        // Below the bottom edge it is the straight distance to it
+        let flat = (
+            Vec2::new(-50.0, 0.0),
+            Vec2::new(50.0, 0.0),
+            Vec2::new(0.0, 50.0),
+        );
+        assert!(
+            (sdf_triangle(Vec2::new(0.0, -10.0), flat.0, flat.1, flat.2) - 10.0).abs() < BORDER
+        );
+    }
+}
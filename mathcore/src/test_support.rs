@@ -0,0 +1,25 @@
+// Helpers shared by the tests in mathcore, the integration tests include this file with #[path].
+use bevy::prelude::*;
+
+// A small random number generator so the tests are the same every run
+pub struct Lcg(pub u64);
+
+impl Lcg {
+    // Between 0 and 1
+    pub fn next_f32(&mut self) -> f32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    pub fn next_range(&mut self, min: f32, max: f32) -> f32 {
+        min + self.next_f32() * (max - min)
+    }
+
+    // Both coordinates between min and max
+    pub fn next_point(&mut self, min: f32, max: f32) -> Vec2 {
+        Vec2::new(self.next_range(min, max), self.next_range(min, max))
+    }
+}
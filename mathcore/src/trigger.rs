@@ -0,0 +1,238 @@
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+
+use crate::containment::{point_in_aabb, point_in_circle, point_in_convex_polygon, point_in_obb};
+use crate::player::Player;
+use crate::polygon::{classify_point, FillRule, PointClass};
+
+// Sends TriggerEntered and TriggerExited events when a player moves in or out of a TriggerShape.
+pub struct TriggerPlugin;
+
+impl Plugin for TriggerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TriggerEntered>()
+            .add_event::<TriggerExited>()
+            .add_system(check_triggers);
+    }
+}
+
+// A region the player can be inside of. The shape is in the local space of the entity,
+// so it moves with the entity's GlobalTransform and is scaled by it.
+// Every entity can have one, there is no limit on how many regions there are.
+#[derive(Component, Clone, Debug)]
+pub enum TriggerShape {
+    Circle {
+        radius: f32,
+    },
+    // Ignores the rotation of the entity
+    Aabb {
+        half_extents: Vec2,
+    },
+    // Rotates with the entity
+    Obb {
+        half_extents: Vec2,
+    },
+    // The points have to make a convex polygon, it rotates with the entity
+    ConvexPolygon {
+        points: Vec<Vec2>,
+    },
+    // Any polygon, it can be concave or cross itself. Points on the edge count as inside.
+    Polygon {
+        points: Vec<Vec2>,
+        fill_rule: FillRule,
+    },
+}
+
+impl TriggerShape {
+    // Is the world space point inside the shape when the shape is placed at `transform`.
+    pub fn contains(&self, transform: &GlobalTransform, point: Vec2) -> bool {
+        let (scale, rotation, translation) = transform.to_scale_rotation_translation();
+        let center = translation.truncate();
+        let scale = scale.truncate();
+
+        match self {
+            TriggerShape::Circle { radius } => {
+                point_in_circle(point, center, radius * scale.max_element())
+            }
+            TriggerShape::Aabb { half_extents } => {
+                point_in_aabb(point, center, *half_extents * scale)
+            }
+            TriggerShape::Obb { half_extents } => {
+                // The angle of the local x axis is the rotation around z
+                let local_x = rotation * Vec3::X;
+                let angle = local_x.y.atan2(local_x.x);
+                point_in_obb(point, center, *half_extents * scale, angle)
+            }
+            TriggerShape::ConvexPolygon { points } => {
+                point_in_convex_polygon(point, &to_world(transform, points))
+            }
+            TriggerShape::Polygon { points, fill_rule } => {
+                classify_point(point, &to_world(transform, points), *fill_rule)
+                    != PointClass::Outside
+            }
+        }
+    }
+}
+
+fn to_world(transform: &GlobalTransform, points: &[Vec2]) -> Vec<Vec2> {
+    points
+        .iter()
+        .map(|local_point| transform.mul_vec3(local_point.extend(0.0)).truncate())
+        .collect()
+}
+
+// Sent the frame a player goes from outside to inside a region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TriggerEntered {
+    pub player: Entity,
+    pub region: Entity,
+}
+
+// Sent the frame a player goes from inside to outside a region.
+// This is also sent when the player or region stop existing while the player was inside.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TriggerExited {
+    pub player: Entity,
+    pub region: Entity,
+}
+
+// Check every player against every region and compare with last frame to find who entered and exited.
+pub fn check_triggers(
+    player_query: Query<(Entity, &GlobalTransform), With<Player>>,
+    region_query: Query<(Entity, &GlobalTransform, &TriggerShape)>,
+    mut inside_last_frame: Local<HashSet<(Entity, Entity)>>,
+    mut entered_events: EventWriter<TriggerEntered>,
+    mut exited_events: EventWriter<TriggerExited>,
+) {
+    let mut inside_this_frame = HashSet::default();
+
+    for (player, player_transform) in player_query.iter() {
+        let player_position = player_transform.translation().truncate();
+
+        for (region, region_transform, shape) in region_query.iter() {
+            if shape.contains(region_transform, player_position) {
+                inside_this_frame.insert((player, region));
+            }
+        }
+    }
+
+    for &(player, region) in inside_this_frame.difference(&inside_last_frame) {
+        entered_events.send(TriggerEntered { player, region });
+    }
+
+    for &(player, region) in inside_last_frame.difference(&inside_this_frame) {
+        exited_events.send(TriggerExited { player, region });
+    }
+
+    *inside_last_frame = inside_this_frame;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::event::Events;
+    use bevy::hierarchy::HierarchyPlugin;
+    use bevy::transform::TransformPlugin;
+
+    #[derive(Default)]
+    struct Sent {
+        entered: Vec<TriggerEntered>,
+        exited: Vec<TriggerExited>,
+    }
+
+    impl Sent {
+        fn entered(&self, player: Entity, region: Entity) -> usize {
+            let event = TriggerEntered { player, region };
+            self.entered.iter().filter(|sent| **sent == event).count()
+        }
+
+        fn exited(&self, player: Entity, region: Entity) -> usize {
+            let event = TriggerExited { player, region };
+            self.exited.iter().filter(|sent| **sent == event).count()
+        }
+    }
+
+    // A few frames, so the transforms are propagated before the triggers see them
+    fn run(app: &mut App, sent: &mut Sent) {
+        for _ in 0..3 {
+            app.update();
+            sent.entered
+                .extend(app.world.resource_mut::<Events<TriggerEntered>>().drain());
+            sent.exited
+                .extend(app.world.resource_mut::<Events<TriggerExited>>().drain());
+        }
+    }
+
+    fn move_to(app: &mut App, entity: Entity, position: Vec2) {
+        app.world.get_mut::<Transform>(entity).unwrap().translation = position.extend(0.0);
+    }
+
+    #[test]
+    fn enter_and_exit_once() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(TransformPlugin)
+            .add_plugin(HierarchyPlugin)
+            .add_plugin(TriggerPlugin);
+
+        let spawn_at = |app: &mut App, position: Vec2| {
+            app.world
+                .spawn()
+                .insert_bundle(TransformBundle::from_transform(
+                    Transform::from_translation(position.extend(0.0)),
+                ))
+                .id()
+        };
+
+        // Two regions overlapping between x = -10 and x = 50
+        let circle = spawn_at(&mut app, Vec2::ZERO);
+        app.world
+            .entity_mut(circle)
+            .insert(TriggerShape::Circle { radius: 50.0 });
+        let square = spawn_at(&mut app, Vec2::new(40.0, 0.0));
+        app.world.entity_mut(square).insert(TriggerShape::Aabb {
+            half_extents: Vec2::new(50.0, 50.0),
+        });
+
+        let first = spawn_at(&mut app, Vec2::new(-200.0, 0.0));
+        let second = spawn_at(&mut app, Vec2::new(300.0, 0.0));
+        app.world.entity_mut(first).insert(Player);
+        app.world.entity_mut(second).insert(Player);
+
+        let mut sent = Sent::default();
+        run(&mut app, &mut sent);
+        assert!(sent.entered.is_empty() && sent.exited.is_empty());
+
+        // The first player walks into both regions, stays a while and walks out again
+        move_to(&mut app, first, Vec2::new(20.0, 0.0));
+        run(&mut app, &mut sent);
+        run(&mut app, &mut sent);
+        assert_eq!(sent.entered(first, circle), 1);
+        assert_eq!(sent.entered(first, square), 1);
+        assert!(sent.exited.is_empty());
+
+        move_to(&mut app, first, Vec2::new(-200.0, 0.0));
+        run(&mut app, &mut sent);
+        assert_eq!(sent.exited(first, circle), 1);
+        assert_eq!(sent.exited(first, square), 1);
+
+        // The second player goes into both, and the square goes away with the player in it
+        move_to(&mut app, second, Vec2::new(30.0, 0.0));
+        run(&mut app, &mut sent);
+        assert_eq!(sent.entered(second, circle), 1);
+        assert_eq!(sent.entered(second, square), 1);
+
+        app.world.despawn(square);
+        run(&mut app, &mut sent);
+        assert_eq!(sent.exited(second, square), 1);
+        assert_eq!(sent.exited(second, circle), 0);
+
+        move_to(&mut app, second, Vec2::new(300.0, 0.0));
+        run(&mut app, &mut sent);
+        assert_eq!(sent.exited(second, circle), 1);
+
+        // Exactly one of each for every player and region, nothing else
+        assert_eq!(sent.entered.len(), 4);
+        assert_eq!(sent.exited.len(), 4);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::mesh::MeshBuffers;
+use crate::ray::ray_segment;
+use bevy::prelude::*;
+use std::f32::consts::{PI, TAU};
+
+// How far to either side of a corner the extra rays go, in radians.
+// One of them just misses the corner and carries on to whatever is behind it.
+pub const CORNER_OFFSET: f32 = 1e-4;
+
+// Rays spread evenly all the way around, so open space without walls still gets a round edge
+// and no two rays are more than half a turn apart.
+pub const FILL_RAYS: usize = 32;
+
+// The area that can be seen from `origin` when the segments block the view, as points going
+// counter clockwise around it. Nothing is seen further away than `max_distance`.
+// Rays go at every corner and just past it, so between two neighbouring rays the closest wall
+// is always the same one and the edge between their hits follows that wall.
+pub fn visibility_polygon(origin: Vec2, segments: &[(Vec2, Vec2)], max_distance: f32) -> Vec<Vec2> {
+    let mut angles: Vec<f32> = (0..FILL_RAYS)
+        .map(|i| TAU * i as f32 / FILL_RAYS as f32 - PI)
+        .collect();
+
+    for (start, end) in segments {
+        for corner in [*start, *end] {
+            let to_corner = corner - origin;
+            if to_corner == Vec2::ZERO {
+                continue;
+            }
+            let angle = to_corner.y.atan2(to_corner.x);
+            angles.extend([angle - CORNER_OFFSET, angle, angle + CORNER_OFFSET]);
+        }
+    }
+
+    angles.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    angles.dedup_by(|a, b| (*a - *b).abs() < CORNER_OFFSET * 0.1);
+
+    angles
+        .iter()
+        .map(|angle| {
+            let direction = Vec2::from_angle(*angle);
+            let toi = segments
+                .iter()
+                .filter_map(|(start, end)| {
+                    ray_segment(origin, direction, max_distance, *start, *end)
+                })
+                .map(|hit| hit.toi)
+                .fold(max_distance, f32::min);
+            origin + direction * toi
+        })
+        .collect()
+}
+
+// The polygon filled in, a fan of triangles from the origin.
+pub fn visibility_buffers(origin: Vec2, polygon: &[Vec2]) -> MeshBuffers {
+    let mut buffers = MeshBuffers::new();
+    for (i, point) in polygon.iter().enumerate() {
+        buffers.push_triangle(origin, *point, polygon[(i + 1) % polygon.len()]);
+    }
+    buffers
+}
+
+// Everything outside the polygon out to `distance` from the origin. Between every two
+// neighbouring rays it is the quad from their hits out along the rays.
+pub fn shadow_buffers(origin: Vec2, polygon: &[Vec2], distance: f32) -> MeshBuffers {
+    let mut buffers = MeshBuffers::new();
+    let far = |point: Vec2| origin + (point - origin).normalize_or_zero() * distance;
+
+    for (i, point) in polygon.iter().enumerate() {
+        let next = polygon[(i + 1) % polygon.len()];
+        buffers.push_triangle(*point, far(*point), far(next));
+        buffers.push_triangle(*point, far(next), next);
+    }
+    buffers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::polygon::winding_number;
+
+    fn box_segments(half_extents: Vec2) -> Vec<(Vec2, Vec2)> {
+        let corners = [
+            Vec2::new(-half_extents.x, -half_extents.y),
+            Vec2::new(half_extents.x, -half_extents.y),
+            Vec2::new(half_extents.x, half_extents.y),
+            Vec2::new(-half_extents.x, half_extents.y),
+        ];
+        (0..4).map(|i| (corners[i], corners[(i + 1) % 4])).collect()
+    }
+
+    fn area(polygon: &[Vec2]) -> f32 {
+        (0..polygon.len())
+            .map(|i| polygon[i].perp_dot(polygon[(i + 1) % polygon.len()]))
+            .sum::<f32>()
+            / 2.0
+    }
+
+    #[test]
+    fn nothing_in_the_way() {
+        let origin = Vec2::new(5.0, -3.0);
+        let polygon = visibility_polygon(origin, &[], 100.0);
+        assert_eq!(polygon.len(), FILL_RAYS);
+        for point in polygon.iter() {
+            assert!(((*point - origin).length() - 100.0).abs() < 1e-3);
+        }
+        // Counter clockwise
+        assert!(area(&polygon) > 0.0);
+    }
+
+    #[test]
+    fn inside_a_room() {
+        let half_extents = Vec2::new(200.0, 100.0);
+        let polygon =
+            visibility_polygon(Vec2::new(50.0, 20.0), &box_segments(half_extents), 1000.0);
+
+        // The whole room can be seen, and nothing outside it
+        let room_area = 4.0 * half_extents.x * half_extents.y;
+        assert!((area(&polygon) - room_area).abs() < room_area * 1e-4);
+        for point in polygon.iter() {
+            assert!(
+                point.x.abs() <= half_extents.x + 1e-3 && point.y.abs() <= half_extents.y + 1e-3
+            );
+        }
+    }
+
+    #[test]
+    fn walls_cast_shadows() {
+        let mut segments = box_segments(Vec2::new(200.0, 200.0));
+        // A wall between the origin and the top of the room
+        segments.push((Vec2::new(-40.0, 50.0), Vec2::new(40.0, 50.0)));
+        let origin = Vec2::ZERO;
+        let polygon = visibility_polygon(origin, &segments, 1000.0);
+
+        let seen = |point: Vec2| winding_number(point, &polygon) != 0;
+        assert!(seen(Vec2::new(0.0, 40.0)));
+        assert!(!seen(Vec2::new(0.0, 60.0)));
+        assert!(!seen(Vec2::new(90.0, 190.0)));
+        assert!(!seen(Vec2::new(-90.0, 190.0)));
+        // Past the ends of the wall
+        assert!(seen(Vec2::new(170.0, 190.0)));
+        assert!(seen(Vec2::new(-170.0, 190.0)));
+        assert!(seen(Vec2::new(0.0, -190.0)));
+
+        // The edge of the shadow goes right past the corners of the wall
+        for corner in [Vec2::new(-40.0, 50.0), Vec2::new(40.0, 50.0)] {
+            let behind = corner * 4.0;
+            assert!(
+                polygon.iter().any(|point| point.distance(behind) < 1.0),
+                "no ray past {}",
+                corner
+            );
+        }
+    }
+
+    #[test]
+    fn meshes() {
+        let origin = Vec2::new(10.0, 10.0);
+        let polygon = visibility_polygon(origin, &box_segments(Vec2::new(100.0, 100.0)), 1000.0);
+
+        let lit = visibility_buffers(origin, &polygon);
+        assert_eq!(lit.triangle_count(), polygon.len());
+
+        let shadow = shadow_buffers(origin, &polygon, 1000.0);
+        assert_eq!(shadow.triangle_count(), polygon.len() * 2);
+        // The shadow starts at the walls and goes out to the distance
+        for position in shadow.positions.iter() {
+            let outside_room = position.x.abs() >= 100.0 - 1e-3 || position.y.abs() >= 100.0 - 1e-3;
+            assert!(outside_room, "{} is inside the room", position);
+            assert!(position.distance(origin) <= 1000.0 + 1e-2);
+        }
+    }
+}
@@ -0,0 +1,198 @@
+// Casts the same rays at the same shapes with mathcore::ray and with rapier
+// and checks that they agree on what was hit, how far away and which way it faces.
+
+use bevy::hierarchy::HierarchyPlugin;
+use bevy::prelude::*;
+use bevy::transform::TransformPlugin;
+use bevy_rapier2d::prelude::*;
+use mathcore::ray::{
+    ray_aabb, ray_circle, ray_convex_polygon, ray_obb, ray_segment, RayIntersection,
+};
+
+#[path = "../src/test_support.rs"]
+mod test_support;
+
+use test_support::Lcg;
+
+const EPSILON: f32 = 1e-2;
+
+// Shapes sit this far apart so a ray aimed at one can't reach another
+const SPACING: f32 = 2000.0;
+const MAX_TOI: f32 = 600.0;
+
+enum Shape {
+    Segment(Vec2, Vec2),
+    Aabb(Vec2),
+    Obb(Vec2, f32),
+    Circle(f32),
+    Polygon(Vec<Vec2>),
+}
+
+impl Shape {
+    fn collider(&self) -> Collider {
+        match self {
+            Shape::Segment(start, end) => Collider::segment(*start, *end),
+            Shape::Aabb(half) | Shape::Obb(half, _) => Collider::cuboid(half.x, half.y),
+            Shape::Circle(radius) => Collider::ball(*radius),
+            Shape::Polygon(points) => Collider::convex_polyline(points.clone()).unwrap(),
+        }
+    }
+
+    fn rotation(&self) -> f32 {
+        match self {
+            Shape::Obb(_, rotation) => *rotation,
+            _ => 0.0,
+        }
+    }
+
+    fn cast(
+        &self,
+        center: Vec2,
+        origin: Vec2,
+        direction: Vec2,
+        solid: bool,
+    ) -> Option<RayIntersection> {
+        match self {
+            Shape::Segment(start, end) => {
+                ray_segment(origin, direction, MAX_TOI, center + *start, center + *end)
+            }
+            Shape::Aabb(half) => ray_aabb(origin, direction, MAX_TOI, center, *half, solid),
+            Shape::Obb(half, rotation) => {
+                ray_obb(origin, direction, MAX_TOI, center, *half, *rotation, solid)
+            }
+            Shape::Circle(radius) => ray_circle(origin, direction, MAX_TOI, center, *radius, solid),
+            Shape::Polygon(points) => {
+                let points: Vec<Vec2> = points.iter().map(|point| center + *point).collect();
+                ray_convex_polygon(origin, direction, MAX_TOI, &points, solid)
+            }
+        }
+    }
+
+    fn is_segment(&self) -> bool {
+        matches!(self, Shape::Segment(..))
+    }
+}
+
+fn random_shape(rng: &mut Lcg, kind: usize) -> Shape {
+    match kind {
+        0 => Shape::Segment(rng.next_point(-150.0, 150.0), rng.next_point(-150.0, 150.0)),
+        1 => Shape::Aabb(rng.next_point(10.0, 150.0)),
+        2 => Shape::Obb(
+            rng.next_point(10.0, 150.0),
+            rng.next_range(-std::f32::consts::PI, std::f32::consts::PI),
+        ),
+        3 => Shape::Circle(rng.next_range(10.0, 150.0)),
+        _ => {
+            // Points around a circle in order are always convex
+            let count = 3 + (rng.next_f32() * 6.0) as usize;
+            let mut angles: Vec<f32> = (0..count)
+                .map(|_| rng.next_range(0.0, std::f32::consts::TAU))
+                .collect();
+            angles.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let radius = rng.next_range(30.0, 150.0);
+            let points: Vec<Vec2> = angles
+                .iter()
+                .map(|angle| Vec2::from_angle(*angle) * radius)
+                .collect();
+
+            // Too thin to tell apart from its own edges
+            let area: f32 = (0..count)
+                .map(|i| points[i].perp_dot(points[(i + 1) % count]))
+                .sum();
+            if area < radius * radius {
+                Shape::Circle(radius)
+            } else {
+                Shape::Polygon(points)
+            }
+        }
+    }
+}
+
+#[test]
+fn rays_agree_with_rapier() {
+    let mut rng = Lcg(19);
+    let shapes: Vec<(Vec2, Shape)> = (0..100)
+        .map(|i| {
+            let center = Vec2::new((i % 10) as f32, (i / 10) as f32) * SPACING;
+            (center, random_shape(&mut rng, i % 5))
+        })
+        .collect();
+
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(TransformPlugin)
+        .add_plugin(HierarchyPlugin)
+        .add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0));
+
+    for (center, shape) in &shapes {
+        app.world
+            .spawn()
+            .insert_bundle(TransformBundle::from_transform(
+                Transform::from_translation(center.extend(0.0))
+                    .with_rotation(Quat::from_rotation_z(shape.rotation())),
+            ))
+            .insert(shape.collider());
+    }
+
+    // Let rapier add the colliders to its query pipeline
+    app.update();
+    app.update();
+    let context = app.world.resource::<RapierContext>();
+
+    let mut hits = 0;
+    for (center, shape) in &shapes {
+        for _ in 0..50 {
+            // Aim somewhere near the shape so about half the rays hit it
+            let origin = *center + rng.next_point(-300.0, 300.0);
+            let direction = (*center + rng.next_point(-150.0, 150.0) - origin).normalize();
+            let solid = rng.next_f32() < 0.5;
+
+            let ours = shape.cast(*center, origin, direction, solid);
+            let theirs = context
+                .cast_ray_and_get_normal(origin, direction, MAX_TOI, solid, QueryFilter::default())
+                .map(|(_, intersection)| intersection);
+
+            match (ours, theirs) {
+                (None, None) => {}
+                (Some(ours), Some(theirs)) => {
+                    hits += 1;
+                    assert!(
+                        (ours.toi - theirs.toi).abs() < EPSILON,
+                        "toi {} is not rapier's {} from {} along {}",
+                        ours.toi,
+                        theirs.toi,
+                        origin,
+                        direction
+                    );
+
+                    // Starting inside there is no normal for a solid shape, and which way the
+                    // normal of the far side faces is up to the implementation
+                    let inside = matches!(
+                        shape.cast(*center, origin, direction, true),
+                        Some(hit) if hit.toi == 0.0
+                    );
+                    if inside {
+                        continue;
+                    }
+                    let agree = if shape.is_segment() {
+                        // Either side of a segment is fine
+                        ours.normal.dot(theirs.normal).abs() > 1.0 - EPSILON
+                    } else {
+                        ours.normal.abs_diff_eq(theirs.normal, EPSILON)
+                    };
+                    assert!(
+                        agree,
+                        "normal {} is not rapier's {} from {} along {}",
+                        ours.normal, theirs.normal, origin, direction
+                    );
+                }
+                (ours, theirs) => panic!(
+                    "from {} along {} we hit {:?} but rapier hit {:?}",
+                    origin, direction, ours, theirs
+                ),
+            }
+        }
+    }
+
+    assert!(hits > 1000, "only {} rays hit", hits);
+}